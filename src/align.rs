@@ -0,0 +1,289 @@
+// Pairwise alignment with affine gaps (Gotoh), global (Needleman-Wunsch) or local (Smith-Waterman).
+use anyhow::{Result, bail};
+
+use crate::bio::is_gap;
+
+const BLOSUM62_ORDER: &str = "ARNDCQEGHILKMFPSTWYVBZX*";
+pub const N_RESIDUES: usize = 24;
+#[rustfmt::skip]
+const BLOSUM62: [[i8; N_RESIDUES]; N_RESIDUES] = [
+    [ 4,-1,-2,-2, 0,-1,-1, 0,-2,-1,-1,-1,-1,-2,-1, 1, 0,-3,-2, 0,-2,-1, 0,-4],
+    [-1, 5, 0,-2,-3, 1, 0,-2, 0,-3,-2, 2,-1,-3,-2,-1,-1,-3,-2,-3,-1, 0,-1,-4],
+    [-2, 0, 6, 1,-3, 0, 0, 0, 1,-3,-3, 0,-2,-3,-2, 1, 0,-4,-2,-3, 3, 0,-1,-4],
+    [-2,-2, 1, 6,-3, 0, 2,-1,-1,-3,-4,-1,-3,-3,-1, 0,-1,-4,-3,-3, 4, 1,-1,-4],
+    [ 0,-3,-3,-3, 9,-3,-4,-3,-3,-1,-1,-3,-1,-2,-3,-1,-1,-2,-2,-1,-3,-3,-2,-4],
+    [-1, 1, 0, 0,-3, 5, 2,-2, 0,-3,-2, 1, 0,-3,-1, 0,-1,-2,-1,-2, 0, 3,-1,-4],
+    [-1, 0, 0, 2,-4, 2, 5,-2, 0,-3,-3, 1,-2,-3,-1, 0,-1,-3,-2,-2, 1, 4,-1,-4],
+    [ 0,-2, 0,-1,-3,-2,-2, 6,-2,-4,-4,-2,-3,-3,-2, 0,-2,-2,-3,-3,-1,-2,-1,-4],
+    [-2, 0, 1,-1,-3, 0, 0,-2, 8,-3,-3,-1,-2,-1,-2,-1,-2,-2, 2,-3, 0, 0,-1,-4],
+    [-1,-3,-3,-3,-1,-3,-3,-4,-3, 4, 2,-3, 1, 0,-3,-2,-1,-3,-1, 3,-3,-3,-1,-4],
+    [-1,-2,-3,-4,-1,-2,-3,-4,-3, 2, 4,-2, 2, 0,-3,-2,-1,-2,-1, 1,-4,-3,-1,-4],
+    [-1, 2, 0,-1,-3, 1, 1,-2,-1,-3,-2, 5,-1,-3,-1, 0,-1,-3,-2,-2, 0, 1,-1,-4],
+    [-1,-1,-2,-3,-1, 0,-2,-3,-2, 1, 2,-1, 5, 0,-2,-1,-1,-1,-1, 1,-3,-1,-1,-4],
+    [-2,-3,-3,-3,-2,-3,-3,-3,-1, 0, 0,-3, 0, 6,-4,-2,-2, 1, 3,-1,-3,-3,-1,-4],
+    [-1,-2,-2,-1,-3,-1,-1,-2,-2,-3,-3,-1,-2,-4, 7,-1,-1,-4,-3,-2,-2,-1,-2,-4],
+    [ 1,-1, 1, 0,-1, 0, 0, 0,-1,-2,-2, 0,-1,-2,-1, 4, 1,-3,-2,-2, 0, 0, 0,-4],
+    [ 0,-1, 0,-1,-1,-1,-1,-2,-2,-1,-1,-1,-1,-2,-1, 1, 5,-2,-2, 0,-1,-1, 0,-4],
+    [-3,-3,-4,-4,-2,-2,-3,-2,-2,-3,-2,-3,-1, 1,-4,-3,-2,11, 2,-3,-4,-3,-2,-4],
+    [-2,-2,-2,-3,-2,-1,-2,-3, 2,-1,-1,-2,-1, 3,-3,-2,-2, 2, 7,-1,-3,-2,-1,-4],
+    [ 0,-3,-3,-3,-1,-2,-2,-3,-3, 3, 1,-2, 1,-1,-2,-2, 0,-3,-1, 4,-3,-2,-1,-4],
+    [-2,-1, 3, 4,-3, 0, 1,-1, 0,-3,-4, 0,-3,-3,-2, 0,-1,-4,-3,-3, 4, 1,-1,-4],
+    [-1, 0, 0, 1,-3, 3, 4,-2, 0,-3,-3, 1,-1,-3,-1, 0,-1,-3,-2,-2, 1, 4,-1,-4],
+    [ 0,-1,-1,-1,-2,-1,-1,-1,-1,-1,-1,-1,-1,-1,-2, 0, 0,-2,-1,-1,-1,-1,-1,-4],
+    [-4,-4,-4,-4,-4,-4,-4,-4,-4,-4,-4,-4,-4,-4,-4,-4,-4,-4,-4,-4,-4,-4,-4, 1],
+];
+
+#[derive(Clone, Copy, PartialEq)]
+pub enum Mode {
+    Global,
+    Local,
+}
+
+impl Mode {
+    pub fn parse(s: &str) -> Result<Self> {
+        match s.to_lowercase().as_str() {
+            "global" => Ok(Mode::Global),
+            "local" => Ok(Mode::Local),
+            _ => bail!("Alignment mode not understood: '{s}'. Use \"global\" or \"local\"."),
+        }
+    }
+}
+
+// Substitution scores from BLOSUM62 or match/mismatch, and affine gap penalties where a gap of
+// length k costs open + k * extend.
+pub struct Scoring {
+    pub blosum: bool,
+    pub match_score: i32,
+    pub mismatch: i32,
+    pub gap_open: i32,
+    pub gap_extend: i32,
+}
+
+impl Scoring {
+    // Defaults for nucleotides as EDNAFULL without ambiguity, and for proteins as BLAST.
+    pub fn new(protein: bool) -> Self {
+        match protein {
+            false => Scoring { blosum: false, match_score: 5, mismatch: -4, gap_open: 10, gap_extend: 1 },
+            true => Scoring { blosum: true, match_score: 1, mismatch: -1, gap_open: 11, gap_extend: 1 },
+        }
+    }
+
+    pub fn score(&self, a: char, b: char) -> i32 {
+        let (a, b) = (a.to_ascii_uppercase(), b.to_ascii_uppercase());
+        if self.blosum {
+            return BLOSUM62[self.index(a)][self.index(b)] as i32;
+        }
+        let (a, b) = (if a == 'U' { 'T' } else { a }, if b == 'U' { 'T' } else { b });
+        if a == b { self.match_score } else { self.mismatch }
+    }
+
+    // Index of a residue in substitution tables, where unknown residues count as X and for
+    // nucleotides U counts as T.
+    pub fn index(&self, c: char) -> usize {
+        let c = match c.to_ascii_uppercase() {
+            'U' if !self.blosum => 'T',
+            c => c,
+        };
+        BLOSUM62_ORDER.find(c).unwrap_or(22)
+    }
+
+    // Substitution scores of residues by index.
+    pub fn table(&self) -> Vec<[f32; N_RESIDUES]> {
+        let order: Vec<char> = BLOSUM62_ORDER.chars().collect();
+        order.iter().map(|&a| std::array::from_fn(|j| self.score(a, order[j]) as f32)).collect()
+    }
+
+    // Whether aligned residues are similar, i.e. identical or with a positive BLOSUM62 score.
+    pub fn similar(&self, a: char, b: char) -> bool {
+        if is_gap(a) || is_gap(b) {
+            return false;
+        }
+        a.eq_ignore_ascii_case(&b) || (self.blosum && self.score(a, b) > 0)
+    }
+}
+
+pub struct Alignment {
+    // Aligned rows with "-" for gaps.
+    pub a: Vec<char>,
+    pub b: Vec<char>,
+    // 0-based start of the aligned part of each sequence, which is 0 for global alignment.
+    pub a_start: usize,
+    pub b_start: usize,
+    pub score: i32,
+}
+
+// Traceback pointers of the three matrices of a cell packed in a byte: which matrix the best
+// score came from for M (match), X (gap in b), and Y (gap in a), or for M that a local alignment
+// starts at the cell.
+const FROM_M: u8 = 0;
+const FROM_X: u8 = 1;
+const FROM_Y: u8 = 2;
+const START: u8 = 3;
+
+// A column of an alignment of a against b.
+#[derive(Clone, Copy, PartialEq)]
+pub enum Step {
+    Match,
+    // A position of a against a gap.
+    GapB,
+    // A position of b against a gap.
+    GapA,
+}
+
+// Cells of the traceback matrix, a byte each, e.g. two sequences of 16 kb.
+pub const MAX_CELLS: usize = 1 << 28;
+
+// Align n positions against m positions with Gotoh's algorithm given the substitution score of
+// each pair of positions. Returns the columns, the start of the aligned part of each, and the score.
+// Scores take linear memory and traceback a byte per cell, so at most MAX_CELLS cells are aligned.
+pub fn gotoh(
+    n: usize,
+    m: usize,
+    score: impl Fn(usize, usize) -> f32,
+    gap_open: f32,
+    gap_extend: f32,
+    mode: Mode,
+) -> Result<(Vec<Step>, usize, usize, f32)> {
+    let cells = (n + 1).saturating_mul(m + 1);
+    if cells > MAX_CELLS {
+        bail!("Aligning {n} against {m} positions needs {} MB, more than the limit of {} MB.", cells >> 20, MAX_CELLS >> 20);
+    }
+    let neg = f32::NEG_INFINITY;
+    let open = gap_open + gap_extend;
+    let extend = gap_extend;
+    let mut trace = vec![0u8; (n + 1) * (m + 1)];
+    // Scores of the previous and current row for each matrix.
+    let mut prev_m = vec![neg; m + 1];
+    let mut prev_x = vec![neg; m + 1];
+    let mut prev_y = vec![neg; m + 1];
+    prev_m[0] = 0.0;
+    for j in 1..=m {
+        prev_y[j] = match mode {
+            Mode::Global => -(gap_open + j as f32 * extend),
+            Mode::Local => neg,
+        };
+        trace[j] = FROM_Y << 4;
+    }
+    // Preferring M then X on ties, written as two selections rather than branches for speed.
+    let max3 = |m: f32, x: f32, y: f32| -> (f32, u8) {
+        let (score, from) = if x > m { (x, FROM_X) } else { (m, FROM_M) };
+        if y > score { (y, FROM_Y) } else { (score, from) }
+    };
+    let mut best = (0.0, 0, 0);
+    let (mut cur_m, mut cur_x, mut cur_y) = (vec![neg; m + 1], vec![neg; m + 1], vec![neg; m + 1]);
+    for i in 1..=n {
+        cur_m[0] = neg;
+        cur_y[0] = neg;
+        cur_x[0] = match mode {
+            Mode::Global => -(gap_open + i as f32 * extend),
+            Mode::Local => neg,
+        };
+        let trace_row = &mut trace[i * (m + 1)..(i + 1) * (m + 1)];
+        trace_row[0] = FROM_X << 2;
+        // The cell to the left is kept in locals, which is faster than reading it back.
+        let (mut left_m, mut left_x, mut left_y) = (cur_m[0], cur_x[0], cur_y[0]);
+        for j in 1..=m {
+            let (mut diag, mut from_m) = max3(prev_m[j - 1], prev_x[j - 1], prev_y[j - 1]);
+            // A local alignment starts at this pair rather than extending one that doesn't score.
+            if mode == Mode::Local && diag <= 0.0 {
+                diag = 0.0;
+                from_m = START;
+            }
+            let score_m = diag + score(i - 1, j - 1);
+            let (score_x, from_x) = max3(prev_m[j] - open, prev_x[j] - extend, prev_y[j] - open);
+            let (score_y, from_y) = max3(left_m - open, left_x - open, left_y - extend);
+            (left_m, left_x, left_y) = (score_m, score_x, score_y);
+            cur_m[j] = score_m;
+            cur_x[j] = score_x;
+            cur_y[j] = score_y;
+            trace_row[j] = from_m | from_x << 2 | from_y << 4;
+            if mode == Mode::Local && score_m > best.0 {
+                best = (score_m, i, j);
+            }
+        }
+        std::mem::swap(&mut prev_m, &mut cur_m);
+        std::mem::swap(&mut prev_x, &mut cur_x);
+        std::mem::swap(&mut prev_y, &mut cur_y);
+    }
+
+    // Start the traceback from the end for global and the best match for local alignment.
+    let (score, mut i, mut j, mut matrix) = match mode {
+        Mode::Global => {
+            let (score, matrix) = if n == 0 && m == 0 {
+                (0.0, FROM_M)
+            } else if n == 0 {
+                (prev_y[m], FROM_Y)
+            } else if m == 0 {
+                (prev_x[0], FROM_X)
+            } else {
+                max3(prev_m[m], prev_x[m], prev_y[m])
+            };
+            (score, n, m, matrix)
+        }
+        Mode::Local => (best.0, best.1, best.2, FROM_M),
+    };
+    let mut steps = vec![];
+    while i > 0 || j > 0 {
+        if mode == Mode::Local && (i == 0 || j == 0) {
+            break;
+        }
+        let pointers = trace[i * (m + 1) + j];
+        match matrix {
+            FROM_M => {
+                let from = pointers & 3;
+                steps.push(Step::Match);
+                i -= 1;
+                j -= 1;
+                if from == START {
+                    break;
+                }
+                matrix = from;
+            }
+            FROM_X => {
+                steps.push(Step::GapB);
+                matrix = pointers >> 2 & 3;
+                i -= 1;
+            }
+            _ => {
+                steps.push(Step::GapA);
+                matrix = pointers >> 4 & 3;
+                j -= 1;
+            }
+        }
+    }
+    steps.reverse();
+    Ok((steps, i, j, score))
+}
+
+// Align two sequences.
+pub fn align(a: &[char], b: &[char], scoring: &Scoring, mode: Mode) -> Result<Alignment> {
+    let table = scoring.table();
+    let (index_a, index_b): (Vec<usize>, Vec<usize>) =
+        (a.iter().map(|&c| scoring.index(c)).collect(), b.iter().map(|&c| scoring.index(c)).collect());
+    let score = |i: usize, j: usize| table[index_a[i]][index_b[j]];
+    let (steps, a_start, b_start, score) =
+        gotoh(a.len(), b.len(), score, scoring.gap_open as f32, scoring.gap_extend as f32, mode)?;
+    let (mut row_a, mut row_b) = (Vec::with_capacity(steps.len()), Vec::with_capacity(steps.len()));
+    let (mut i, mut j) = (a_start, b_start);
+    for step in steps {
+        match step {
+            Step::Match => {
+                row_a.push(a[i]);
+                row_b.push(b[j]);
+                i += 1;
+                j += 1;
+            }
+            Step::GapB => {
+                row_a.push(a[i]);
+                row_b.push('-');
+                i += 1;
+            }
+            Step::GapA => {
+                row_a.push('-');
+                row_b.push(b[j]);
+                j += 1;
+            }
+        }
+    }
+    Ok(Alignment { a: row_a, b: row_b, a_start, b_start, score: score as i32 })
+}
@@ -0,0 +1,311 @@
+use anyhow::{Result, bail};
+use phf::phf_map;
+use std::collections::HashMap;
+use std::io::BufRead;
+use yansi::Color::{self, *};
+use yansi::{Painted, Style};
+
+use crate::ansi_colors::{Char, Highlight, is_light, supported};
+use crate::bio::is_gap;
+use crate::colorschemes::parse_color;
+use crate::inout::open;
+use crate::render::{Line, restyle, sequence};
+
+// Default colors for common feature types. Other types get a color from PALETTE.
+static FEATURE_COLORS: phf::Map<&'static str, Color> = phf_map! {
+    "gene" => Rgb(190, 190, 190),
+    "mrna" => Rgb(150, 150, 150),
+    "exon" => Rgb(100, 149, 237),
+    "cds" => Rgb(60, 179, 113),
+    "five_prime_utr" => Rgb(255, 165, 0),
+    "three_prime_utr" => Rgb(255, 140, 0),
+    "domain" => Rgb(186, 85, 211),
+    "protein_domain" => Rgb(186, 85, 211),
+    "primer" => Rgb(240, 230, 80),
+    "primer_binding_site" => Rgb(240, 230, 80),
+    "variant" => Rgb(220, 50, 50),
+    "sequence_variant" => Rgb(220, 50, 50),
+    "snv" => Rgb(220, 50, 50),
+    "repeat_region" => Rgb(120, 120, 120),
+    "misc_feature" => Rgb(72, 209, 204),
+};
+
+static PALETTE: [Color; 6] = [
+    Rgb(230, 159, 0),
+    Rgb(86, 180, 233),
+    Rgb(0, 158, 115),
+    Rgb(240, 228, 66),
+    Rgb(0, 114, 178),
+    Rgb(204, 121, 167),
+];
+
+// An interval on a sequence in 0-based, end exclusive, ungapped coordinates.
+pub struct Feature {
+    pub start: usize,
+    pub end: usize,
+    // GFF type or BED name, used for coloring.
+    pub kind: String,
+    // Label to show in annotation rows.
+    pub label: String,
+}
+
+// Features keyed by sequence ID.
+pub type Features = HashMap<String, Vec<Feature>>;
+
+pub fn read_bed(path: &str, features: &mut Features) -> Result<()> {
+    for (i, line) in open(path)?.lines().enumerate() {
+        let line = line?;
+        if line.is_empty() || line.starts_with('#') || line.starts_with("track") || line.starts_with("browser") {
+            continue;
+        }
+        let fields: Vec<&str> = line.split('\t').collect();
+        if fields.len() < 3 {
+            bail!("{path}:{}: BED line needs at least 3 columns.", i + 1);
+        }
+        let (Ok(start), Ok(end)) = (fields[1].parse::<usize>(), fields[2].parse::<usize>()) else {
+            bail!("{path}:{}: BED coordinates not understood: '{}' and '{}'.", i + 1, fields[1], fields[2]);
+        };
+        let name = fields.get(3).copied().unwrap_or("feature");
+        features.entry(fields[0].to_string()).or_default().push(Feature {
+            start,
+            end,
+            kind: name.to_string(),
+            label: name.to_string(),
+        });
+    }
+    Ok(())
+}
+
+pub fn read_gff(path: &str, features: &mut Features) -> Result<()> {
+    for (i, line) in open(path)?.lines().enumerate() {
+        let line = line?;
+        if line.starts_with("##FASTA") {
+            break;
+        }
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+        let fields: Vec<&str> = line.split('\t').collect();
+        if fields.len() < 9 {
+            bail!("{path}:{}: GFF line needs 9 columns.", i + 1);
+        }
+        let (Ok(start), Ok(end)) = (fields[3].parse::<usize>(), fields[4].parse::<usize>()) else {
+            bail!("{path}:{}: GFF coordinates not understood: '{}' and '{}'.", i + 1, fields[3], fields[4]);
+        };
+        let attribute = |key: &str| {
+            fields[8].split(';').find_map(|kv| kv.trim().strip_prefix(key)?.strip_prefix('='))
+        };
+        let kind = fields[2];
+        let label = attribute("Name").or(attribute("ID")).unwrap_or(kind);
+        features.entry(fields[0].to_string()).or_default().push(Feature {
+            start: start.saturating_sub(1),
+            end,
+            kind: kind.to_string(),
+            label: label.to_string(),
+        });
+    }
+    Ok(())
+}
+
+// Parse a GenBank or EMBL feature location, e.g. "complement(join(<1..100,200..>300))", into
+// 0-based end exclusive intervals. Positions in other entries, e.g. "J00194.1:100..202", are skipped.
+fn parse_location(location: &str) -> Vec<(usize, usize)> {
+    let mut intervals = vec![];
+    for part in location.split([',', '(', ')']) {
+        let part = part.trim();
+        if part.contains(':') {
+            continue;
+        }
+        let (start, end) = match part.split_once("..").or(part.split_once('^')) {
+            Some((start, end)) => (start, end),
+            None => (part, part),
+        };
+        let parse = |s: &str| s.trim_start_matches(['<', '>']).parse::<usize>().ok();
+        if let (Some(start), Some(end)) = (parse(start), parse(end)) {
+            intervals.push((start.saturating_sub(1), end));
+        }
+    }
+    intervals
+}
+
+// Read features of the given types from the FEATURES table in GenBank or EMBL annotation lines.
+// An empty list of types means all types except "source". Features with multiple intervals, e.g.
+// joined exons of a CDS, give a feature for each interval.
+pub fn read_flat_file_features(meta: &[String], kinds: &[String]) -> Vec<Feature> {
+    let mut entries: Vec<(String, String, Vec<String>)> = vec![];
+    let mut in_table = false;
+    for line in meta {
+        // EMBL feature lines have the same layout as GenBank after the "FT" prefix.
+        let line = match line.strip_prefix("FT") {
+            Some(rest) => rest,
+            None if line.starts_with("FEATURES") => {
+                in_table = true;
+                continue;
+            }
+            None if in_table && line.starts_with("     ") => &line[2..],
+            None => {
+                in_table = false;
+                continue;
+            }
+        };
+        let (key, value) = (line.get(3..19).unwrap_or("").trim(), line.get(19..).unwrap_or("").trim());
+        if !key.is_empty() {
+            entries.push((key.to_string(), value.to_string(), vec![]));
+        } else if let Some((_, location, qualifiers)) = entries.last_mut() {
+            match value.strip_prefix('/') {
+                Some(qualifier) => qualifiers.push(qualifier.to_string()),
+                // Continued location or qualifier value.
+                None => match qualifiers.last_mut() {
+                    Some(qualifier) => qualifier.push_str(value),
+                    None => location.push_str(value),
+                },
+            }
+        }
+    }
+
+    let mut features = vec![];
+    for (kind, location, qualifiers) in entries {
+        let lower = kind.to_lowercase();
+        if kinds.is_empty() && lower == "source" || !kinds.is_empty() && !kinds.contains(&lower) {
+            continue;
+        }
+        let qualifier = |key: &str| {
+            qualifiers.iter().find_map(|q| Some(q.strip_prefix(key)?.strip_prefix('=')?.trim_matches('"')))
+        };
+        let label = ["label", "gene", "locus_tag", "product"]
+            .into_iter()
+            .find_map(qualifier)
+            .unwrap_or(&kind);
+        for (start, end) in parse_location(&location) {
+            features.push(Feature { start, end, kind: kind.clone(), label: label.to_string() });
+        }
+    }
+    features
+}
+
+// Read a file with a feature type and a color on each line, separated by tab, comma, or semicolon.
+pub fn read_feature_colors(path: &str) -> Result<HashMap<String, Color>> {
+    let mut colors = HashMap::new();
+    for line in open(path)?.lines() {
+        let line = line?;
+        if let Some((kind, coltext)) = line.split_once(['\t', ',', ';']) {
+            let col = parse_color(coltext).map_err(anyhow::Error::msg)?;
+            colors.insert(kind.trim().to_lowercase(), col);
+        }
+    }
+    Ok(colors)
+}
+
+pub enum AnnotationMode {
+    // Show features as rows beneath each line of sequence.
+    Row,
+    // Background color of the covered residues by feature type.
+    Color,
+    Highlight(Highlight),
+}
+
+impl AnnotationMode {
+    pub fn parse(s: &str) -> Result<Self, &'static str> {
+        match s.to_lowercase().as_str() {
+            "row" => Ok(AnnotationMode::Row),
+            "color" => Ok(AnnotationMode::Color),
+            s => Highlight::parse(s).map(AnnotationMode::Highlight),
+        }
+    }
+}
+
+pub struct AnnotationOpts {
+    pub features: Features,
+    // Types of features to show from the FEATURES table of GenBank and EMBL records, where an
+    // empty list means all types except "source". None to not show them.
+    pub flat_file: Option<Vec<String>>,
+    pub colors: HashMap<String, Color>,
+    pub mode: AnnotationMode,
+}
+
+impl AnnotationOpts {
+    pub fn color(&self, kind: &str) -> Color {
+        let kind = kind.to_lowercase();
+        if let Some(&col) = self.colors.get(&kind).or(FEATURE_COLORS.get(&kind)) {
+            return col;
+        }
+        let hash = kind.bytes().fold(0usize, |h, b| h.wrapping_mul(31).wrapping_add(b as usize));
+        PALETTE[hash % PALETTE.len()]
+    }
+}
+
+// Assign features to as few rows as possible such that features in a row don't overlap.
+fn pack<'a>(features: &[&'a Feature]) -> Vec<Vec<&'a Feature>> {
+    let mut sorted = features.to_vec();
+    sorted.sort_by_key(|f| (f.start, f.end));
+    let mut rows: Vec<Vec<&Feature>> = vec![];
+    for feature in sorted {
+        match rows.iter_mut().find(|row| row.last().unwrap().end <= feature.start) {
+            Some(row) => row.push(feature),
+            None => rows.push(vec![feature]),
+        }
+    }
+    rows
+}
+
+// Show features on a sequence, which may be wrapped over multiple lines and contain gaps.
+// Features cover any gaps within them.
+pub fn annotate(seq: &mut [Line], features: &[&Feature], opts: &AnnotationOpts) {
+    if features.is_empty() {
+        return;
+    }
+    let (positions, chars) = sequence(seq);
+    let residues: Vec<usize> = (0..chars.len()).filter(|&k| !is_gap(chars[k])).collect();
+    // Range of indices into positions covered by a feature, if it is within the sequence.
+    let span = |f: &Feature| {
+        let first = *residues.get(f.start)?;
+        let last = *residues.get(f.end.checked_sub(1)?.min(residues.len() - 1))?;
+        (f.start < f.end).then_some(first..=last)
+    };
+
+    match opts.mode {
+        AnnotationMode::Row => {
+            for row in pack(features) {
+                let mut tracks: Vec<Vec<Char>> = seq
+                    .iter()
+                    .map(|line| (0..line.chars.len()).map(|_| Char::Unstyled(' ')).collect())
+                    .collect();
+                for feature in row {
+                    let Some(span) = span(feature) else { continue };
+                    let bg = opts.color(&feature.kind);
+                    let style = Style::new().bg(supported(bg)).fg(if is_light(bg) { Black } else { White });
+                    let mut label = feature.label.chars();
+                    for k in span {
+                        let (i, j) = positions[k];
+                        let value = label.next().unwrap_or(' ');
+                        tracks[i][j] = Char::Styled(Painted { value, style });
+                    }
+                }
+                for (line, mut track) in seq.iter_mut().zip(tracks) {
+                    // Padding only up to the last feature.
+                    while let Some(Char::Unstyled(' ')) = track.last() {
+                        track.pop();
+                    }
+                    line.tracks.push(track);
+                }
+            }
+        }
+        AnnotationMode::Color => {
+            for feature in features {
+                let Some(span) = span(feature) else { continue };
+                let bg = opts.color(&feature.kind);
+                for k in span {
+                    restyle(seq, positions[k], |style| style.bg(supported(bg)));
+                }
+            }
+        }
+        AnnotationMode::Highlight(hl) => {
+            for feature in features {
+                let Some(span) = span(feature) else { continue };
+                for k in span {
+                    restyle(seq, positions[k], |style| hl.apply(style));
+                }
+            }
+        }
+    }
+}
@@ -10,6 +10,8 @@ use yansi::{
     Painted, Style,
 };
 
+use crate::colorschemes::parse_color;
+
 pub static COLOR_NAMES: phf::Map<&'static str, Color> = phf_map! {
     "black"         => Black,
     "red"           => Red,
@@ -94,20 +96,127 @@ pub fn ansi256(col: Color) -> u8 {
         BrightCyan => 14,
         BrightWhite => 15,
         Fixed(idx) => idx,
-        Rgb(r, g, b) => ansi256_from_rgb(&[r, g, b]),
+        Rgb(r, g, b) => ansi256_from_rgb([r, g, b]),
         Primary => 15, // not known but not used
     }
 }
 
+// A color as the terminal can show it, i.e. downgraded to 256 colors without truecolor support,
+// as done for colorschemes.
+pub fn supported(col: Color) -> Color {
+    if anstyle_query::truecolor() { col } else { Fixed(ansi256(col)) }
+}
+
+// A way of marking chars on top of their colorscheme style, e.g. for consensus.
+#[derive(Clone, Copy, Debug)]
+pub enum Highlight {
+    Bold,
+    Dim,
+    Italic,
+    Underline,
+    Invert,
+    Strike,
+    Bg(Color),
+}
+
+impl Highlight {
+    // Parse an attribute name, otherwise a color which is applied to the background.
+    pub fn parse(s: &str) -> Result<Self, &'static str> {
+        match s.to_lowercase().as_str() {
+            "bold" => Ok(Highlight::Bold),
+            "dim" => Ok(Highlight::Dim),
+            "italic" => Ok(Highlight::Italic),
+            "underline" => Ok(Highlight::Underline),
+            "invert" | "reverse" => Ok(Highlight::Invert),
+            "strike" => Ok(Highlight::Strike),
+            _ => parse_color(s).map(Highlight::Bg),
+        }
+    }
+
+    pub fn apply(self, style: Style) -> Style {
+        match self {
+            Highlight::Bold => style.bold(),
+            Highlight::Dim => style.dim(),
+            Highlight::Italic => style.italic(),
+            Highlight::Underline => style.underline(),
+            Highlight::Invert => style.invert(),
+            Highlight::Strike => style.strike(),
+            Highlight::Bg(col) => style.bg(col),
+        }
+    }
+}
+
+// How to treat lowercase chars, e.g. soft-masked bases or A2M insert states.
+#[derive(Clone, Copy, Debug)]
+pub enum Case {
+    Sensitive,
+    // Color lowercase like uppercase.
+    Insensitive,
+    // Color lowercase like uppercase and mark them.
+    Highlight(Highlight),
+    // Remove lowercase chars from sequences.
+    Hide,
+}
+
+impl Case {
+    pub fn parse(s: &str) -> Result<Self, &'static str> {
+        match s.to_lowercase().as_str() {
+            "sensitive" => Ok(Case::Sensitive),
+            "insensitive" => Ok(Case::Insensitive),
+            "hide" => Ok(Case::Hide),
+            s => Highlight::parse(s).map(Case::Highlight),
+        }
+    }
+
+    pub fn hide(self) -> bool {
+        matches!(self, Case::Hide)
+    }
+
+    // Give lowercase chars the style of their uppercase unless they have their own.
+    pub fn apply(self, styles: &mut HashMap<char, Style>) {
+        let highlight = match self {
+            Case::Sensitive | Case::Hide => return,
+            Case::Insensitive => None,
+            Case::Highlight(hl) => Some(hl),
+        };
+        let uppers: Vec<(char, Style)> = styles
+            .iter()
+            .filter(|(c, _)| c.is_uppercase())
+            .map(|(&c, &style)| (c, style))
+            .collect();
+        for (c, style) in uppers {
+            for lower in c.to_lowercase() {
+                styles.entry(lower).or_insert(style);
+            }
+        }
+        if let Some(hl) = highlight {
+            for (c, style) in styles.iter_mut() {
+                if c.is_lowercase() {
+                    *style = hl.apply(*style);
+                }
+            }
+        }
+    }
+}
+
 pub fn ansi_byte(c: char) -> [u8; 1] {
     let mut b = [0; 1];
     c.encode_utf8(&mut b);
     b
 }
 
+// Whether a style has any attributes, e.g. bold, which have to be reset explicitly.
+fn has_attributes(style: &Style) -> bool {
+    let mut attributes = *style;
+    attributes.foreground = None;
+    attributes.background = None;
+    attributes != Style::new()
+}
+
 pub fn write_ansi(
     buf: &mut (impl Write + ?Sized),
     styles: &HashMap<char, Style>,
+    hide_lowercase: bool,
     text: &str,
 ) -> io::Result<usize> {
     let reset = "\x1B[0m".as_bytes();
@@ -115,22 +224,27 @@ pub fn write_ansi(
     // Only call reset when necessary (only when streaming).
     let mut fg = false;
     let mut bg = false;
+    let mut attributes = false;
     for c in text.chars() {
+        if hide_lowercase && c.is_lowercase() {
+            continue;
+        }
         match styles.get(&c) {
             Some(style) => {
                 let _fg = style.foreground.is_some();
                 let _bg = style.background.is_some();
-                if (fg && !_fg) || (bg && !_bg) {
+                if (fg && !_fg) || (bg && !_bg) || attributes {
                     n_bytes += buf.write(reset)?;
                 }
                 fg = _fg;
                 bg = _bg;
+                attributes = has_attributes(style);
                 n_bytes += buf.write(style.prefix().as_bytes())?;
-                n_bytes += buf.write(&ansi_byte(c))?;
+                n_bytes += buf.write(c.encode_utf8(&mut [0; 4]).as_bytes())?;
             }
             None => {
                 n_bytes += buf.write(reset)?;
-                n_bytes += buf.write(&ansi_byte(c))?;
+                n_bytes += buf.write(c.encode_utf8(&mut [0; 4]).as_bytes())?;
             }
         };
     }
@@ -138,6 +252,147 @@ pub fn write_ansi(
     Ok(n_bytes)
 }
 
+// Escape sequences precomputed for each ASCII char, so streaming is a table lookup per byte and
+// runs of chars with the same style share a single escape sequence.
+pub struct StyleTable {
+    // Index into prefixes for each byte, where 0 is unstyled and HIDDEN is removed.
+    ids: [u16; 256],
+    prefixes: Vec<Vec<u8>>,
+    // Whether a style sets foreground, background, and any attributes, which decides whether
+    // switching to another style needs a reset first.
+    kinds: Vec<(bool, bool, bool)>,
+}
+
+const HIDDEN: u16 = u16::MAX;
+
+impl StyleTable {
+    // None if a styled char is not ASCII.
+    pub fn new(styles: &HashMap<char, Style>, hide_lowercase: bool) -> Option<Self> {
+        let mut table = StyleTable { ids: [0; 256], prefixes: vec![vec![]], kinds: vec![(false, false, false)] };
+        let mut unique: Vec<Style> = vec![];
+        for (&c, style) in styles {
+            if !c.is_ascii() {
+                return None;
+            }
+            let id = match unique.iter().position(|s| s == style) {
+                Some(i) => i + 1,
+                None => {
+                    unique.push(*style);
+                    table.prefixes.push(style.prefix().as_bytes().to_vec());
+                    table.kinds.push((style.foreground.is_some(), style.background.is_some(), has_attributes(style)));
+                    unique.len()
+                }
+            };
+            table.ids[c as usize] = id as u16;
+        }
+        if hide_lowercase {
+            table.ids[b'a' as usize..=b'z' as usize].fill(HIDDEN);
+        }
+        Some(table)
+    }
+
+    // Write text with a prefix before each run of chars with the same style and a reset after.
+    pub fn write(&self, buf: &mut Vec<u8>, text: &[u8]) {
+        let mut current = 0;
+        for &b in text {
+            let id = self.ids[b as usize];
+            if id == current {
+                buf.push(b);
+                continue;
+            }
+            if id == HIDDEN {
+                continue;
+            }
+            let (fg, bg, attributes) = self.kinds[current as usize];
+            let (new_fg, new_bg, _) = self.kinds[id as usize];
+            if current != 0 && (id == 0 || (fg && !new_fg) || (bg && !new_bg) || attributes) {
+                buf.extend_from_slice(RESET);
+            }
+            buf.extend_from_slice(&self.prefixes[id as usize]);
+            buf.push(b);
+            current = id;
+        }
+        if current != 0 {
+            buf.extend_from_slice(RESET);
+        }
+    }
+}
+
+const RESET: &[u8] = b"\x1B[0m";
+
+// A part of a line that may contain ANSI escape sequences, e.g. from grep --color or seqcol itself.
+pub enum Segment<'a> {
+    // Visible text and whether it is styled by preceding SGR sequences.
+    Text(&'a str, bool),
+    Escape(&'a str),
+}
+
+// End of the escape sequence starting at i: CSI sequences such as SGR "ESC[...m", OSC sequences
+// ended by BEL or ST (ESC followed by a backslash), and otherwise ESC followed by a single char.
+fn escape_end(bytes: &[u8], i: usize) -> usize {
+    let n = bytes.len();
+    match bytes.get(i + 1) {
+        Some(b'[') => {
+            let mut j = i + 2;
+            while j < n && !(0x40..=0x7E).contains(&bytes[j]) {
+                j += 1;
+            }
+            (j + 1).min(n)
+        }
+        Some(b']') => {
+            let mut j = i + 2;
+            while j < n && bytes[j] != 0x07 && !(bytes[j] == 0x1B && bytes.get(j + 1) == Some(&b'\\')) {
+                j += 1;
+            }
+            if bytes.get(j) == Some(&0x1B) { (j + 2).min(n) } else { (j + 1).min(n) }
+        }
+        Some(_) => i + 2,
+        None => i + 1,
+    }
+}
+
+pub fn segments(line: &str) -> Vec<Segment<'_>> {
+    let bytes = line.as_bytes();
+    let mut segments = vec![];
+    let mut styled = false;
+    let mut start = 0;
+    let mut i = 0;
+    while i < bytes.len() {
+        if bytes[i] != 0x1B {
+            i += 1;
+            continue;
+        }
+        if start < i {
+            segments.push(Segment::Text(&line[start..i], styled));
+        }
+        let end = escape_end(bytes, i);
+        let escape = &line[i..end];
+        if let Some(params) = escape.strip_prefix("\x1B[").and_then(|e| e.strip_suffix('m')) {
+            // Text is unstyled after a reset or only turning attributes and colors off.
+            styled = !params
+                .split(';')
+                .all(|p| matches!(p.parse().unwrap_or(0), 0 | 22..=25 | 27..=29 | 39 | 49));
+        }
+        segments.push(Segment::Escape(escape));
+        i = end;
+        start = end;
+    }
+    if start < line.len() {
+        segments.push(Segment::Text(&line[start..], styled));
+    }
+    segments
+}
+
+pub fn strip_ansi(line: &str) -> String {
+    segments(line)
+        .into_iter()
+        .filter_map(|segment| match segment {
+            Segment::Text(text, _) => Some(text),
+            Segment::Escape(_) => None,
+        })
+        .collect()
+}
+
 // To easily distinguish between formatted chars of sequences and any other text.
 // Why not use Painted with no style? Because coloring might be disabled while we still may want to
 // recognise a char as being part of a sequence.
@@ -150,13 +405,19 @@ impl Char {
     pub fn write(&self, buf: &mut (impl Write + ?Sized)) -> Result<usize, Error> {
         match &self {
             Char::Styled(painted) => buf.write(painted.to_string().as_bytes()),
-            Char::Unstyled(c) => buf.write(&ansi_byte(*c)),
+            Char::Unstyled(c) => buf.write(c.encode_utf8(&mut [0; 4]).as_bytes()),
         }
     }
 }
 
-pub fn to_painted(styles: &HashMap<char, Style>, text: &str) -> impl Iterator<Item = Char> {
-    text.chars().map(|c| to_painted_char(styles, c))
+pub fn to_painted(
+    styles: &HashMap<char, Style>,
+    hide_lowercase: bool,
+    text: &str,
+) -> impl Iterator<Item = Char> {
+    text.chars()
+        .filter(move |c| !(hide_lowercase && c.is_lowercase()))
+        .map(|c| to_painted_char(styles, c))
 }
 
 fn to_painted_char(styles: &HashMap<char, Style>, c: char) -> Char {
@@ -15,3 +15,137 @@ pub static ALPHABETS: phf::Map<&'static str, &'static str> = phf_map! {
     "aaxnogap" => "ARNDCQEGHILKMFPSTWYVBZX",
     "allnogap" => "ACGTURNDQEHILKMFPSWYVBZX",
 };
+
+// One letter codes of residues in PDB and mmCIF files, including common modified residues.
+pub static RESIDUE_CODES: phf::Map<&'static str, char> = phf_map! {
+    "ALA" => 'A', "ARG" => 'R', "ASN" => 'N', "ASP" => 'D', "CYS" => 'C',
+    "GLN" => 'Q', "GLU" => 'E', "GLY" => 'G', "HIS" => 'H', "ILE" => 'I',
+    "LEU" => 'L', "LYS" => 'K', "MET" => 'M', "PHE" => 'F', "PRO" => 'P',
+    "SER" => 'S', "THR" => 'T', "TRP" => 'W', "TYR" => 'Y', "VAL" => 'V',
+    "SEC" => 'U', "PYL" => 'O', "ASX" => 'B', "GLX" => 'Z', "UNK" => 'X',
+    "MSE" => 'M', "HSD" => 'H', "HSE" => 'H', "HSP" => 'H', "HID" => 'H', "HIE" => 'H',
+    "DA" => 'A', "DC" => 'C', "DG" => 'G', "DT" => 'T', "DU" => 'U', "DI" => 'I',
+    "A" => 'A', "C" => 'C', "G" => 'G', "U" => 'U', "T" => 'T', "I" => 'I', "N" => 'N',
+};
+
+pub fn is_gap(c: char) -> bool {
+    c == '-' || c == '.'
+}
+
+// IUPAC aware, preserving case and gaps. Unknown chars are returned unchanged. A complements to U
+// for RNA.
+pub fn complement(c: char, rna: bool) -> char {
+    let comp = match c.to_ascii_uppercase() {
+        'A' if rna => 'U',
+        'A' => 'T',
+        'T' | 'U' => 'A',
+        'C' => 'G',
+        'G' => 'C',
+        'R' => 'Y',
+        'Y' => 'R',
+        'K' => 'M',
+        'M' => 'K',
+        'B' => 'V',
+        'V' => 'B',
+        'D' => 'H',
+        'H' => 'D',
+        // S, W, N are their own complement.
+        other => other,
+    };
+    if c.is_ascii_lowercase() { comp.to_ascii_lowercase() } else { comp }
+}
+
+// Whether a sequence is RNA, i.e. has U but no T.
+pub fn is_rna(lines: &[String]) -> bool {
+    let has = |bases: [char; 2]| lines.iter().any(|line| line.contains(bases));
+    has(['U', 'u']) && !has(['T', 't'])
+}
+
+// IUPAC code of a set of nucleotides, keeping case if all have the same case. U counts as T.
+pub fn iupac_code(bases: &[char]) -> Option<char> {
+    let mut set = [false; 4];
+    for c in bases {
+        match c.to_ascii_uppercase() {
+            'A' => set[0] = true,
+            'C' => set[1] = true,
+            'G' => set[2] = true,
+            'T' | 'U' => set[3] = true,
+            _ => return None,
+        }
+    }
+    let code = match set {
+        [true, false, false, false] => 'A',
+        [false, true, false, false] => 'C',
+        [false, false, true, false] => 'G',
+        [false, false, false, true] => 'T',
+        [true, false, true, false] => 'R',
+        [false, true, false, true] => 'Y',
+        [false, true, true, false] => 'S',
+        [true, false, false, true] => 'W',
+        [false, false, true, true] => 'K',
+        [true, true, false, false] => 'M',
+        [false, true, true, true] => 'B',
+        [true, false, true, true] => 'D',
+        [true, true, false, true] => 'H',
+        [true, true, true, false] => 'V',
+        [true, true, true, true] => 'N',
+        [false, false, false, false] => return None,
+    };
+    if bases.iter().all(|c| c.is_ascii_lowercase()) { Some(code.to_ascii_lowercase()) } else { Some(code) }
+}
+
+// Guess whether a sequence is nucleotides rather than amino acids, allowing a few other chars
+// such as IUPAC ambiguity codes.
+pub fn is_nucleotide(seq: &[char]) -> bool {
+    let residues = seq.iter().filter(|&&c| !is_gap(c));
+    let (mut n, mut n_nucl) = (0, 0);
+    for c in residues {
+        n += 1;
+        if "ACGTUN".contains(c.to_ascii_uppercase()) {
+            n_nucl += 1;
+        }
+    }
+    n > 0 && n_nucl * 10 >= n * 9
+}
+
+// A region of a named sequence in 0-based, end exclusive coordinates.
+#[derive(Clone, Debug)]
+pub struct Region {
+    pub name: String,
+    pub start: usize,
+    pub end: Option<usize>,
+}
+
+impl Region {
+    // Parse samtools style "NAME", "NAME:START", or "NAME:START-END" with 1-based, inclusive
+    // coordinates that may contain thousands separators.
+    pub fn parse(s: &str) -> Result<Self, String> {
+        let err = || format!("Region not understood: '{s}'. Use NAME, NAME:START, or NAME:START-END.");
+        let Some((name, range)) = s.rsplit_once(':') else {
+            return Ok(Region { name: s.to_string(), start: 0, end: None });
+        };
+        let number = |n: &str| n.replace(',', "").parse::<usize>().map_err(|_| err());
+        let (start, end) = match range.split_once('-') {
+            None => (number(range)?, None),
+            Some((start, "")) => (number(start)?, None),
+            Some((start, end)) => (number(start)?, Some(number(end)?)),
+        };
+        if start == 0 || end.is_some_and(|end| end < start) {
+            return Err(err());
+        }
+        Ok(Region { name: name.to_string(), start: start - 1, end })
+    }
+
+    pub fn contains(&self, pos: usize) -> bool {
+        pos >= self.start && self.end.is_none_or(|end| pos < end)
+    }
+}
+
+impl std::fmt::Display for Region {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self.end {
+            None => write!(f, "{}:{}", self.name, self.start + 1),
+            Some(end) => write!(f, "{}:{}-{}", self.name, self.start + 1, end),
+        }
+    }
+}
@@ -0,0 +1,310 @@
+use phf::phf_map;
+use std::collections::HashMap;
+use yansi::{Painted, Style};
+
+use crate::ansi_colors::{Char, Highlight};
+use crate::bio::{complement, is_gap};
+use crate::render::{Line, restyle, sequence};
+
+// NCBI genetic codes, https://www.ncbi.nlm.nih.gov/Taxonomy/Utils/wprintgc.cgi
+// Codons are ordered TTT, TTC, TTA, TTG, TCT, ..., GGG, i.e. bases in the order TCAG.
+pub struct GeneticCode {
+    pub name: &'static str,
+    aas: &'static str,
+    starts: &'static str,
+}
+
+pub static GENETIC_CODES: phf::Map<u8, GeneticCode> = phf_map! {
+    1u8 => GeneticCode {
+        name: "Standard",
+        aas:    "FFLLSSSSYY**CC*WLLLLPPPPHHQQRRRRIIIMTTTTNNKKSSRRVVVVAAAADDEEGGGG",
+        starts: "---M---------------M---------------M----------------------------",
+    },
+    2u8 => GeneticCode {
+        name: "Vertebrate Mitochondrial",
+        aas:    "FFLLSSSSYY**CC*WLLLLPPPPHHQQRRRRIIMMTTTTNNKKSS**VVVVAAAADDEEGGGG",
+        starts: "--------------------------------MMMM---------------M------------",
+    },
+    3u8 => GeneticCode {
+        name: "Yeast Mitochondrial",
+        aas:    "FFLLSSSSYY**CCWWTTTTPPPPHHQQRRRRIIMMTTTTNNKKSSRRVVVVAAAADDEEGGGG",
+        starts: "----------------------------------MM---------------M------------",
+    },
+    4u8 => GeneticCode {
+        name: "Mold, Protozoan, and Coelenterate Mitochondrial and Mycoplasma/Spiroplasma",
+        aas:    "FFLLSSSSYY**CCWWLLLLPPPPHHQQRRRRIIIMTTTTNNKKSSRRVVVVAAAADDEEGGGG",
+        starts: "--MM---------------M------------MMMM---------------M------------",
+    },
+    5u8 => GeneticCode {
+        name: "Invertebrate Mitochondrial",
+        aas:    "FFLLSSSSYY**CCWWLLLLPPPPHHQQRRRRIIMMTTTTNNKKSSSSVVVVAAAADDEEGGGG",
+        starts: "---M----------------------------MMMM---------------M------------",
+    },
+    6u8 => GeneticCode {
+        name: "Ciliate, Dasycladacean and Hexamita Nuclear",
+        aas:    "FFLLSSSSYYQQCC*WLLLLPPPPHHQQRRRRIIIMTTTTNNKKSSRRVVVVAAAADDEEGGGG",
+        starts: "-----------------------------------M----------------------------",
+    },
+    9u8 => GeneticCode {
+        name: "Echinoderm and Flatworm Mitochondrial",
+        aas:    "FFLLSSSSYY**CCWWLLLLPPPPHHQQRRRRIIIMTTTTNNNKSSSSVVVVAAAADDEEGGGG",
+        starts: "-----------------------------------M---------------M------------",
+    },
+    10u8 => GeneticCode {
+        name: "Euplotid Nuclear",
+        aas:    "FFLLSSSSYY**CCCWLLLLPPPPHHQQRRRRIIIMTTTTNNKKSSRRVVVVAAAADDEEGGGG",
+        starts: "-----------------------------------M----------------------------",
+    },
+    11u8 => GeneticCode {
+        name: "Bacterial, Archaeal and Plant Plastid",
+        aas:    "FFLLSSSSYY**CC*WLLLLPPPPHHQQRRRRIIIMTTTTNNKKSSRRVVVVAAAADDEEGGGG",
+        starts: "---M---------------M------------MMMM---------------M------------",
+    },
+    12u8 => GeneticCode {
+        name: "Alternative Yeast Nuclear",
+        aas:    "FFLLSSSSYY**CC*WLLLSPPPPHHQQRRRRIIIMTTTTNNKKSSRRVVVVAAAADDEEGGGG",
+        starts: "-------------------M---------------M----------------------------",
+    },
+    13u8 => GeneticCode {
+        name: "Ascidian Mitochondrial",
+        aas:    "FFLLSSSSYY**CCWWLLLLPPPPHHQQRRRRIIMMTTTTNNKKSSGGVVVVAAAADDEEGGGG",
+        starts: "---M------------------------------MM---------------M------------",
+    },
+    14u8 => GeneticCode {
+        name: "Alternative Flatworm Mitochondrial",
+        aas:    "FFLLSSSSYYY*CCWWLLLLPPPPHHQQRRRRIIIMTTTTNNNKSSSSVVVVAAAADDEEGGGG",
+        starts: "-----------------------------------M----------------------------",
+    },
+    15u8 => GeneticCode {
+        name: "Blepharisma Nuclear",
+        aas:    "FFLLSSSSYY*QCC*WLLLLPPPPHHQQRRRRIIIMTTTTNNKKSSRRVVVVAAAADDEEGGGG",
+        starts: "-----------------------------------M----------------------------",
+    },
+    16u8 => GeneticCode {
+        name: "Chlorophycean Mitochondrial",
+        aas:    "FFLLSSSSYY*LCC*WLLLLPPPPHHQQRRRRIIIMTTTTNNKKSSRRVVVVAAAADDEEGGGG",
+        starts: "-----------------------------------M----------------------------",
+    },
+    21u8 => GeneticCode {
+        name: "Trematode Mitochondrial",
+        aas:    "FFLLSSSSYY**CCWWLLLLPPPPHHQQRRRRIIMMTTTTNNNKSSSSVVVVAAAADDEEGGGG",
+        starts: "-----------------------------------M---------------M------------",
+    },
+    22u8 => GeneticCode {
+        name: "Scenedesmus obliquus Mitochondrial",
+        aas:    "FFLLSS*SYY*LCC*WLLLLPPPPHHQQRRRRIIIMTTTTNNKKSSRRVVVVAAAADDEEGGGG",
+        starts: "-----------------------------------M----------------------------",
+    },
+    23u8 => GeneticCode {
+        name: "Thraustochytrium Mitochondrial",
+        aas:    "FF*LSSSSYY**CC*WLLLLPPPPHHQQRRRRIIIMTTTTNNKKSSRRVVVVAAAADDEEGGGG",
+        starts: "--------------------------------M--M---------------M------------",
+    },
+    24u8 => GeneticCode {
+        name: "Rhabdopleuridae Mitochondrial",
+        aas:    "FFLLSSSSYY**CCWWLLLLPPPPHHQQRRRRIIIMTTTTNNKKSSSKVVVVAAAADDEEGGGG",
+        starts: "---M---------------M---------------M---------------M------------",
+    },
+    25u8 => GeneticCode {
+        name: "Candidate Division SR1 and Gracilibacteria",
+        aas:    "FFLLSSSSYY**CCGWLLLLPPPPHHQQRRRRIIIMTTTTNNKKSSRRVVVVAAAADDEEGGGG",
+        starts: "---M-------------------------------M---------------M------------",
+    },
+    26u8 => GeneticCode {
+        name: "Pachysolen tannophilus Nuclear",
+        aas:    "FFLLSSSSYY**CC*WLLLAPPPPHHQQRRRRIIIMTTTTNNKKSSRRVVVVAAAADDEEGGGG",
+        starts: "-------------------M---------------M----------------------------",
+    },
+    // In 27, 28, and 31 stop codons that also code for an amino acid translate to it.
+    27u8 => GeneticCode {
+        name: "Karyorelict Nuclear",
+        aas:    "FFLLSSSSYYQQCCWWLLLLPPPPHHQQRRRRIIIMTTTTNNKKSSRRVVVVAAAADDEEGGGG",
+        starts: "-----------------------------------M----------------------------",
+    },
+    28u8 => GeneticCode {
+        name: "Condylostoma Nuclear",
+        aas:    "FFLLSSSSYYQQCCWWLLLLPPPPHHQQRRRRIIIMTTTTNNKKSSRRVVVVAAAADDEEGGGG",
+        starts: "-----------------------------------M----------------------------",
+    },
+    29u8 => GeneticCode {
+        name: "Mesodinium Nuclear",
+        aas:    "FFLLSSSSYYYYCC*WLLLLPPPPHHQQRRRRIIIMTTTTNNKKSSRRVVVVAAAADDEEGGGG",
+        starts: "-----------------------------------M----------------------------",
+    },
+    30u8 => GeneticCode {
+        name: "Peritrich Nuclear",
+        aas:    "FFLLSSSSYYEECC*WLLLLPPPPHHQQRRRRIIIMTTTTNNKKSSRRVVVVAAAADDEEGGGG",
+        starts: "-----------------------------------M----------------------------",
+    },
+    31u8 => GeneticCode {
+        name: "Blastocrithidia Nuclear",
+        aas:    "FFLLSSSSYYEECCWWLLLLPPPPHHQQRRRRIIIMTTTTNNKKSSRRVVVVAAAADDEEGGGG",
+        starts: "-----------------------------------M----------------------------",
+    },
+    33u8 => GeneticCode {
+        name: "Cephalodiscidae Mitochondrial",
+        aas:    "FFLLSSSSYYY*CCWWLLLLPPPPHHQQRRRRIIIMTTTTNNKKSSSKVVVVAAAADDEEGGGG",
+        starts: "---M---------------M---------------M---------------M------------",
+    },
+};
+
+// Index of a base in the TCAG order used by the genetic code tables.
+fn base_index(c: char) -> Option<usize> {
+    match c.to_ascii_uppercase() {
+        'T' | 'U' => Some(0),
+        'C' => Some(1),
+        'A' => Some(2),
+        'G' => Some(3),
+        _ => None,
+    }
+}
+
+#[derive(Clone, Copy, PartialEq)]
+pub enum Kind {
+    Start,
+    Stop,
+    Other,
+}
+
+impl GeneticCode {
+    pub fn get(id: u8) -> Option<&'static GeneticCode> {
+        GENETIC_CODES.get(&id)
+    }
+
+    // Amino acid for a codon or X if it contains anything other than ACGTU.
+    pub fn translate(&self, codon: [char; 3]) -> (char, Kind) {
+        let mut idx = 0;
+        for c in codon {
+            match base_index(c) {
+                None => return ('X', Kind::Other),
+                Some(i) => idx = idx * 4 + i,
+            }
+        }
+        let aa = self.aas.as_bytes()[idx] as char;
+        let kind = if aa == '*' {
+            Kind::Stop
+        } else if self.starts.as_bytes()[idx] == b'M' {
+            Kind::Start
+        } else {
+            Kind::Other
+        };
+        (aa, kind)
+    }
+}
+
+// Reading frame 1, 2, 3 on the forward strand or -1, -2, -3 on the reverse strand.
+#[derive(Clone, Copy, PartialEq)]
+pub struct Frame(i8);
+
+impl Frame {
+    pub const ALL: [Frame; 6] = [Frame(1), Frame(2), Frame(3), Frame(-1), Frame(-2), Frame(-3)];
+
+    pub fn parse(s: &str) -> Result<Self, String> {
+        match s.trim().trim_start_matches('+').parse::<i8>() {
+            Ok(f) if (1..=3).contains(&f.abs()) => Ok(Frame(f)),
+            _ => Err(format!("Invalid reading frame '{s}'. Use 1, 2, 3, -1, -2, or -3.")),
+        }
+    }
+
+    pub fn parse_list(s: &str) -> Result<Vec<Self>, String> {
+        if s == "all" {
+            return Ok(Frame::ALL.to_vec());
+        }
+        s.split(',').map(Frame::parse).collect()
+    }
+}
+
+// A codon found in a sequence given by the positions of its three bases in the sequence.
+pub struct Codon {
+    pub pos: [usize; 3],
+    pub aa: char,
+    pub kind: Kind,
+}
+
+// Translate the given sequence in a reading frame, skipping gaps.
+// Positions are indices into the given slice.
+pub fn codons(seq: &[char], frame: Frame, code: &GeneticCode) -> Vec<Codon> {
+    let mut bases: Vec<usize> = (0..seq.len()).filter(|&i| !is_gap(seq[i])).collect();
+    if frame.0 < 0 {
+        bases.reverse();
+    }
+    let offset = (frame.0.unsigned_abs() - 1) as usize;
+    bases
+        .get(offset..)
+        .unwrap_or_default()
+        .chunks_exact(3)
+        .map(|chunk| {
+            let pos = [chunk[0], chunk[1], chunk[2]];
+            let codon = if frame.0 < 0 {
+                pos.map(|i| complement(seq[i], false))
+            } else {
+                pos.map(|i| seq[i])
+            };
+            let (aa, kind) = code.translate(codon);
+            Codon { pos, aa, kind }
+        })
+        .collect()
+}
+
+pub struct CodonOpts {
+    pub code: &'static GeneticCode,
+    pub frame: Frame,
+    // Color each base by the amino acid of its codon.
+    pub color: bool,
+    // Frames to show translations of beneath each line.
+    pub translate: Vec<Frame>,
+    pub start: Option<Highlight>,
+    pub stop: Option<Highlight>,
+}
+
+impl CodonOpts {
+    fn style(&self, styles: &HashMap<char, Style>, codon: &Codon) -> Style {
+        let style = styles.get(&codon.aa).copied().unwrap_or_default();
+        match (codon.kind, self.start, self.stop) {
+            (Kind::Start, Some(hl), _) => hl.apply(style),
+            (Kind::Stop, _, Some(hl)) => hl.apply(style),
+            _ => style,
+        }
+    }
+}
+
+// Color and/or translate the sequence of a record which may be wrapped over multiple lines.
+// Only styled chars, i.e. those matching the regex filters, are considered part of the sequence.
+pub fn paint_codons(seq: &mut [Line], styles: &HashMap<char, Style>, opts: &CodonOpts) {
+    let (positions, chars) = sequence(seq);
+
+    for &frame in &opts.translate {
+        let mut tracks: Vec<Vec<Char>> = seq
+            .iter()
+            .map(|line| (0..line.chars.len()).map(|_| Char::Unstyled(' ')).collect())
+            .collect();
+        // Show each amino acid beneath the middle base of its codon.
+        for codon in codons(&chars, frame, opts.code) {
+            let (i, j) = positions[codon.pos[1]];
+            let style = opts.style(styles, &codon);
+            tracks[i][j] = Char::Styled(Painted { value: codon.aa, style });
+        }
+        for (line, mut track) in seq.iter_mut().zip(tracks) {
+            // Padding only up to the last amino acid.
+            while let Some(Char::Unstyled(' ')) = track.last() {
+                track.pop();
+            }
+            line.tracks.push(track);
+        }
+    }
+
+    if opts.color {
+        // Bases that are not part of a full codon are left unstyled.
+        for (k, &pos) in positions.iter().enumerate() {
+            if !is_gap(chars[k]) {
+                restyle(seq, pos, |_| Style::new());
+            }
+        }
+        for codon in codons(&chars, opts.frame, opts.code) {
+            let style = opts.style(styles, &codon);
+            for k in codon.pos {
+                restyle(seq, positions[k], |_| style);
+            }
+        }
+    }
+}
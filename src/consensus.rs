@@ -0,0 +1,340 @@
+use anyhow::{Result, bail};
+use std::borrow::Borrow;
+use std::collections::HashSet;
+use std::thread;
+
+use crate::ansi_colors::{Char, Highlight};
+use crate::bio::{is_gap, iupac_code};
+use crate::render::Line;
+
+/// How lowercase chars are counted for consensus.
+pub enum ConsensusCase {
+    /// Lowercase and uppercase are different chars.
+    Sensitive,
+    /// Lowercase chars are not counted nor highlighted.
+    Ignore,
+    /// Lowercase chars are counted as uppercase.
+    Fold,
+}
+
+impl ConsensusCase {
+    pub fn parse(s: &str) -> Result<Self> {
+        match s.to_lowercase().as_str() {
+            "sensitive" => Ok(ConsensusCase::Sensitive),
+            "ignore" => Ok(ConsensusCase::Ignore),
+            "fold" => Ok(ConsensusCase::Fold),
+            _ => Err(anyhow::anyhow!("Invalid consensus case value: '{}'. Use 'sensitive', 'ignore', or 'fold'.", s)),
+        }
+    }
+
+    /// The char to count for consensus, if any.
+    pub fn count(&self, c: char) -> Option<char> {
+        match self {
+            ConsensusCase::Sensitive => Some(c),
+            ConsensusCase::Ignore => (!c.is_lowercase()).then_some(c),
+            ConsensusCase::Fold => Some(c.to_ascii_uppercase()),
+        }
+    }
+}
+
+/// How to resolve columns where multiple chars have the max count.
+pub enum Ties {
+    /// No consensus.
+    None,
+    /// The first char alphabetically.
+    Alphabetical,
+    /// The IUPAC ambiguity code of tied nucleotides, otherwise no consensus.
+    Iupac,
+}
+
+impl Ties {
+    pub fn parse(s: &str) -> Result<Self> {
+        match s.to_lowercase().as_str() {
+            "none" => Ok(Ties::None),
+            "alphabetical" => Ok(Ties::Alphabetical),
+            "iupac" => Ok(Ties::Iupac),
+            _ => bail!("Invalid consensus ties value: '{s}'. Use 'none', 'alphabetical', or 'iupac'."),
+        }
+    }
+}
+
+pub struct ConsensusOpts {
+    pub alphabet: Option<HashSet<char>>,
+    pub case: ConsensusCase,
+    /// Min fraction of the counted chars of a column for the consensus.
+    pub threshold: f64,
+    /// Gaps are neither counted nor highlighted.
+    pub exclude_gaps: bool,
+    pub ties: Ties,
+    /// Weight sequences by Henikoff position-based weights.
+    pub henikoff: bool,
+}
+
+impl ConsensusOpts {
+    // The char to count for consensus, if any. Only styled chars are counted, which will
+    // effectively apply the regex filters.
+    fn counted(&self, ch: &Char) -> Option<char> {
+        let Char::Styled(painted) = ch else {
+            return None;
+        };
+        let c = self.case.count(painted.value)?;
+        if self.exclude_gaps && is_gap(c) {
+            return None;
+        }
+        self.alphabet.as_ref().is_none_or(|alphabet| alphabet.contains(&c)).then_some(c)
+    }
+}
+
+// The consensus of a column, along with the chars matching it, which are multiple for an IUPAC
+// code of tied nucleotides.
+pub struct Column {
+    pub char: char,
+    matches: Vec<char>,
+}
+
+const UNSEEN: usize = usize::MAX;
+
+// Char occurrences at each column of lines, kept as a compact array of counts per residue, so
+// counts of long alignments take little memory. Counts are weighted by sequence.
+pub struct Counts {
+    // Index into residues for ASCII chars, or UNSEEN.
+    ascii: [usize; 128],
+    residues: Vec<char>,
+    // Counts by residue index then column.
+    counts: Vec<Vec<f64>>,
+    columns: usize,
+}
+
+impl Counts {
+    pub fn new() -> Self {
+        Counts { ascii: [UNSEEN; 128], residues: vec![], counts: vec![], columns: 0 }
+    }
+
+    fn index(&self, c: char) -> usize {
+        match c.is_ascii() {
+            true => self.ascii[c as usize],
+            false => self.residues.iter().position(|&r| r == c).unwrap_or(UNSEEN),
+        }
+    }
+
+    fn residue(&mut self, c: char) -> usize {
+        let i = self.index(c);
+        if i != UNSEEN {
+            return i;
+        }
+        self.residues.push(c);
+        self.counts.push(vec![0.0; self.columns]);
+        if c.is_ascii() {
+            self.ascii[c as usize] = self.residues.len() - 1;
+        }
+        self.residues.len() - 1
+    }
+
+    fn widen(&mut self, columns: usize) {
+        if columns > self.columns {
+            self.columns = columns;
+            for counts in &mut self.counts {
+                counts.resize(columns, 0.0);
+            }
+        }
+    }
+
+    // Count lines with the weight of the sequence of each line.
+    fn count<L: Borrow<Line>>(&mut self, lines: &[L], weights: &[f64], opts: &ConsensusOpts) {
+        self.widen(lines.iter().map(|line| line.borrow().chars.len()).max().unwrap_or(0));
+        for (painted_line, weight) in lines.iter().zip(weights) {
+            for (i, ch) in painted_line.borrow().chars.iter().enumerate() {
+                if let Some(c) = opts.counted(ch) {
+                    let r = self.residue(c);
+                    self.counts[r][i] += weight;
+                }
+            }
+        }
+    }
+
+    fn merge(&mut self, other: Counts) {
+        self.widen(other.columns);
+        for (c, counts) in other.residues.into_iter().zip(other.counts) {
+            let r = self.residue(c);
+            for (total, n) in self.counts[r].iter_mut().zip(counts) {
+                *total += n;
+            }
+        }
+    }
+
+    // Count lines in chunks by the given number of threads.
+    pub fn add<L: Borrow<Line> + Sync>(&mut self, lines: &[L], weights: &[f64], opts: &ConsensusOpts, threads: usize) {
+        if threads <= 1 || lines.len() < 2 {
+            self.count(lines, weights, opts);
+            return;
+        }
+        let size = lines.len().div_ceil(threads);
+        let chunks: Vec<Counts> = thread::scope(|s| {
+            let handles: Vec<_> = lines
+                .chunks(size)
+                .zip(weights.chunks(size))
+                .map(|(chunk, weights)| {
+                    s.spawn(move || {
+                        let mut counts = Counts::new();
+                        counts.count(chunk, weights, opts);
+                        counts
+                    })
+                })
+                .collect();
+            handles.into_iter().map(|handle| handle.join().unwrap()).collect()
+        });
+        for counts in chunks {
+            self.merge(counts);
+        }
+    }
+
+    // Henikoff position-based weight of the sequence of the given lines, given unweighted counts,
+    // as the sum over its chars of 1 / (r * n), where r is the number of different chars in the
+    // column and n is the count of the char. Weights are relative, so they aren't normalized.
+    pub fn henikoff<L: Borrow<Line>>(&self, lines: &[L], opts: &ConsensusOpts) -> f64 {
+        let mut weight = 0.0;
+        for painted_line in lines {
+            for (i, ch) in painted_line.borrow().chars.iter().enumerate() {
+                let Some(c) = opts.counted(ch) else {
+                    continue;
+                };
+                let r = self.index(c);
+                if r == UNSEEN || i >= self.columns {
+                    continue;
+                }
+                let n_different = self.counts.iter().filter(|counts| counts[i] > 0.0).count();
+                weight += 1.0 / (n_different as f64 * self.counts[r][i]);
+            }
+        }
+        weight
+    }
+
+    // Define consensus as the char seen with max (weighted) occurrences at each column,
+    // if its fraction of the column reaches the threshold. Ties are resolved by opts.
+    pub fn consensus(&self, opts: &ConsensusOpts) -> Vec<Option<Column>> {
+        let mut consensus: Vec<Option<Column>> = Vec::with_capacity(self.columns);
+        for i in 0..self.columns {
+            let total: f64 = self.counts.iter().map(|counts| counts[i]).sum();
+            let max = self.counts.iter().map(|counts| counts[i]).fold(0.0, f64::max);
+            if max <= 0.0 || max < opts.threshold * total {
+                consensus.push(None);
+                continue;
+            }
+            // Weighted counts are sums of floats, so equal counts may differ by rounding.
+            let mut tied: Vec<char> = (self.residues.iter().zip(&self.counts))
+                .filter(|(_, counts)| counts[i] >= max * (1.0 - 1e-9))
+                .map(|(c, _)| *c)
+                .collect();
+            tied.sort();
+            let column = match (tied.as_slice(), &opts.ties) {
+                ([c], _) => Some(Column { char: *c, matches: vec![*c] }),
+                (_, Ties::None) => None,
+                (_, Ties::Alphabetical) => Some(Column { char: tied[0], matches: vec![tied[0]] }),
+                (_, Ties::Iupac) => iupac_code(&tied).map(|code| Column { char: code, matches: tied }),
+            };
+            consensus.push(column);
+        }
+        consensus
+    }
+}
+
+// Counts of groups of records, counted in batches of limited size split between threads.
+pub struct Counter<'a> {
+    opts: &'a ConsensusOpts,
+    threads: usize,
+    counts: Vec<Counts>,
+    // Lines and weights of each group not yet counted.
+    batches: Vec<(Vec<Line>, Vec<f64>)>,
+    size: usize,
+}
+
+impl<'a> Counter<'a> {
+    pub fn new(opts: &'a ConsensusOpts, threads: usize) -> Self {
+        Counter { opts, threads, counts: vec![], batches: vec![], size: 0 }
+    }
+
+    pub fn push(&mut self, group: usize, lines: impl IntoIterator<Item = Line>, weight: f64) {
+        while self.counts.len() <= group {
+            self.counts.push(Counts::new());
+            self.batches.push((vec![], vec![]));
+        }
+        let (batch, weights) = &mut self.batches[group];
+        for line in lines {
+            self.size += line.chars.len();
+            batch.push(line);
+            weights.push(weight);
+        }
+        if self.size >= self.threads << 16 {
+            self.flush();
+        }
+    }
+
+    fn flush(&mut self) {
+        for (counts, (batch, weights)) in self.counts.iter_mut().zip(&mut self.batches) {
+            counts.add(batch, weights, self.opts, self.threads);
+            batch.clear();
+            weights.clear();
+        }
+        self.size = 0;
+    }
+
+    pub fn finish(mut self) -> Vec<Counts> {
+        self.flush();
+        self.counts
+    }
+}
+
+// Fraction of the counted chars of lines in columns with a consensus that match it, i.e. that
+// -c/--consensus would highlight.
+pub fn identity(lines: &[Line], consensus: &[Option<Column>], opts: &ConsensusOpts) -> f64 {
+    let (mut same, mut total) = (0, 0);
+    for line in lines {
+        for (ch, column) in line.chars.iter().zip(consensus) {
+            if let (Some(c), Some(column)) = (opts.counted(ch), column) {
+                total += 1;
+                same += column.matches.contains(&c) as usize;
+            }
+        }
+    }
+    if total == 0 { 0.0 } else { same as f64 / total as f64 }
+}
+
+// Apply a highlight to chars matching the consensus of their column, or to chars deviating from
+// it if not highlight_consensus.
+pub fn highlight(
+    lines: &mut [Line],
+    consensus: &[Option<Column>],
+    opts: &ConsensusOpts,
+    highlight_consensus: bool,
+    highlight: Highlight,
+) {
+    // Collect references to chars to highlight (consensus or mutations).
+    let mut painted_to_highlight = vec![];
+    for painted_line in lines {
+        for (i, ch) in painted_line.chars.iter_mut().enumerate() {
+            match consensus.get(i).and_then(|column| column.as_ref()) {
+                None => {}
+                Some(_consensus) => match ch {
+                    Char::Unstyled(_) => {}
+                    Char::Styled(painted) => {
+                        let Some(c) = opts.case.count(painted.value) else {
+                            continue;
+                        };
+                        if opts.exclude_gaps && is_gap(c) {
+                            continue;
+                        }
+                        let is_consensus = _consensus.matches.contains(&c);
+                        if is_consensus == highlight_consensus {
+                            painted_to_highlight.push(painted);
+                        }
+                    }
+                },
+            }
+        }
+    }
+
+    // Apply either an attribute or bg color to highlighted chars.
+    for painted in painted_to_highlight {
+        painted.style = highlight.apply(painted.style);
+    }
+}
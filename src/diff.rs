@@ -0,0 +1,162 @@
+// Pairwise comparison of two sequences, printed as aligned rows with a match line between them.
+use anyhow::{Result, bail};
+use std::collections::HashMap;
+use std::io::{self, Write};
+use std::path::Path;
+use yansi::Style;
+
+use crate::align::{Alignment, Mode, Scoring, align};
+use crate::ansi_colors::{Char, Highlight, to_painted};
+use crate::bio::is_gap;
+use crate::inout::read_lines;
+use crate::records::{Record, records};
+
+pub struct DiffOpts {
+    pub mode: Mode,
+    pub scoring: Scoring,
+    // Alignment columns per block.
+    pub width: usize,
+    pub mismatch: Option<Highlight>,
+}
+
+// A named ungapped sequence.
+pub struct Sequence {
+    pub name: String,
+    pub residues: Vec<char>,
+}
+
+impl Sequence {
+    fn from_record(record: &Record<String>, fallback: &str) -> Self {
+        let residues = record
+            .seq
+            .iter()
+            .flat_map(|line| line.chars())
+            .filter(|&c| if record.blocks { c.is_alphabetic() } else { !c.is_whitespace() && !is_gap(c) })
+            .collect();
+        Sequence { name: record.id().unwrap_or(fallback).to_string(), residues }
+    }
+}
+
+fn read_records(path: &str) -> Result<Vec<Record<String>>> {
+    let lines = read_lines(vec![path.to_string()], 1)?;
+    Ok(records(lines).filter(|record| !record.seq.is_empty()).collect())
+}
+
+// The sequences to compare given as files, "FILE:ID" for a record other than the first, or
+// literal sequences. A single file gives its first two records.
+pub fn read_sequences(a: &str, b: Option<&str>) -> Result<(Sequence, Sequence)> {
+    let read = |spec: &str, fallback: &str| -> Result<Sequence> {
+        if spec == "-" || Path::new(spec).is_file() {
+            return match read_records(spec)?.first() {
+                Some(record) => Ok(Sequence::from_record(record, fallback)),
+                None => bail!("No sequence found in {spec}."),
+            };
+        }
+        if let Some((path, id)) = spec.rsplit_once(':')
+            && Path::new(path).is_file()
+        {
+            return match read_records(path)?.iter().find(|record| record.id() == Some(id)) {
+                Some(record) => Ok(Sequence::from_record(record, fallback)),
+                None => bail!("Sequence '{id}' not found in {path}."),
+            };
+        }
+        if spec.is_empty() || !spec.chars().all(|c| c.is_ascii_alphabetic() || c == '*' || is_gap(c)) {
+            bail!("'{spec}' is neither a file nor a sequence.");
+        }
+        Ok(Sequence { name: fallback.to_string(), residues: spec.chars().filter(|&c| !is_gap(c)).collect() })
+    };
+    match b {
+        Some(b) => Ok((read(a, "A")?, read(b, "B")?)),
+        None => {
+            let records = read_records(a)?;
+            let [first, second, ..] = records.as_slice() else {
+                bail!("Give two sequences to compare, or a file with at least two.");
+            };
+            Ok((Sequence::from_record(first, "A"), Sequence::from_record(second, "B")))
+        }
+    }
+}
+
+// Symbol of an alignment column in the match line.
+fn match_symbol(scoring: &Scoring, a: char, b: char) -> char {
+    if is_gap(a) || is_gap(b) {
+        ' '
+    } else if a.eq_ignore_ascii_case(&b) {
+        '|'
+    } else if scoring.similar(a, b) {
+        ':'
+    } else {
+        '.'
+    }
+}
+
+fn percent(n: usize, total: usize) -> f64 {
+    if total == 0 { 0.0 } else { 100.0 * n as f64 / total as f64 }
+}
+
+struct RowPainter<'a> {
+    styles: &'a HashMap<char, Style>,
+    hide_lowercase: bool,
+    mismatch: Option<Highlight>,
+    name_width: usize,
+    pos_width: usize,
+}
+
+impl RowPainter<'_> {
+    // Write a block of an aligned row between its first and last position, where `pos` is the
+    // position before the block and is advanced past it.
+    fn write(&self, output: &mut (impl Write + ?Sized), name: &str, block: &[char], other: &[char], pos: &mut usize) -> io::Result<()> {
+        let n = block.iter().filter(|&&c| !is_gap(c)).count();
+        let start = if n > 0 { *pos + 1 } else { *pos };
+        *pos += n;
+        let (name_width, pos_width) = (self.name_width, self.pos_width);
+        write!(output, "{name:<name_width$} {start:>pos_width$} ")?;
+        for (&c, &o) in block.iter().zip(other) {
+            for mut ch in to_painted(self.styles, self.hide_lowercase, &c.to_string()) {
+                if let (Char::Styled(painted), Some(hl)) = (&mut ch, self.mismatch)
+                    && !c.eq_ignore_ascii_case(&o)
+                {
+                    painted.style = hl.apply(painted.style);
+                }
+                ch.write(output)?;
+            }
+        }
+        writeln!(output, " {pos}")
+    }
+}
+
+// Write blocks of the two aligned rows with their start and end positions, followed by statistics.
+pub fn write_diff(
+    output: &mut (impl Write + ?Sized),
+    a: &Sequence,
+    b: &Sequence,
+    styles: &HashMap<char, Style>,
+    hide_lowercase: bool,
+    opts: &DiffOpts,
+) -> Result<()> {
+    let Alignment { a: row_a, b: row_b, a_start, b_start, score } = align(&a.residues, &b.residues, &opts.scoring, opts.mode)?;
+    let name_width = a.name.chars().count().max(b.name.chars().count());
+    let pos_width = (a.residues.len().max(b.residues.len())).to_string().len();
+    let (mut pos_a, mut pos_b) = (a_start, b_start);
+    let width = opts.width.max(1);
+    let painter = RowPainter { styles, hide_lowercase, mismatch: opts.mismatch, name_width, pos_width };
+    for (block_a, block_b) in row_a.chunks(width).zip(row_b.chunks(width)) {
+        painter.write(output, &a.name, block_a, block_b, &mut pos_a)?;
+        let symbols: String = block_a.iter().zip(block_b).map(|(&x, &y)| match_symbol(&opts.scoring, x, y)).collect();
+        writeln!(output, "{:pad$}{symbols}", "", pad = name_width + pos_width + 2)?;
+        painter.write(output, &b.name, block_b, block_a, &mut pos_b)?;
+        writeln!(output)?;
+    }
+
+    let length = row_a.len();
+    let columns = || row_a.iter().zip(&row_b);
+    let identity = columns().filter(|&(&x, &y)| !is_gap(x) && x.eq_ignore_ascii_case(&y)).count();
+    let similarity = columns().filter(|&(&x, &y)| opts.scoring.similar(x, y)).count();
+    let gaps = columns().filter(|&(&x, &y)| is_gap(x) || is_gap(y)).count();
+    writeln!(output, "# Length:     {length}")?;
+    writeln!(output, "# Identity:   {identity}/{length} ({:.1}%)", percent(identity, length))?;
+    writeln!(output, "# Similarity: {similarity}/{length} ({:.1}%)", percent(similarity, length))?;
+    writeln!(output, "# Gaps:       {gaps}/{length} ({:.1}%)", percent(gaps, length))?;
+    writeln!(output, "# Score:      {score}")?;
+    Ok(())
+}
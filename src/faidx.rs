@@ -0,0 +1,249 @@
+// Random access to regions of fasta files with samtools compatible .fai indexes, which are created
+// if missing. BGZF compressed fasta is read with a .gzi index of its blocks, also created if missing.
+use anyhow::{Result, bail};
+use flate2::read::MultiGzDecoder;
+use std::fs::File;
+use std::io::{self, BufRead, BufReader, Read, Seek, SeekFrom, Write};
+
+use crate::bio::Region;
+
+// A line of a .fai file.
+struct Entry {
+    name: String,
+    length: u64,
+    // Byte offset of the first base.
+    offset: u64,
+    line_bases: u64,
+    // Bytes per line including the line ending.
+    line_width: u64,
+}
+
+impl Entry {
+    // Byte offset of a 0-based position.
+    fn offset(&self, pos: u64) -> u64 {
+        self.offset + pos / self.line_bases * self.line_width + pos % self.line_bases
+    }
+}
+
+fn read_fai(path: &str) -> Result<Vec<Entry>> {
+    let mut entries = vec![];
+    for line in BufReader::new(File::open(path)?).lines() {
+        let line = line?;
+        let fields: Vec<&str> = line.split('\t').collect();
+        let number = |i: usize| fields.get(i).and_then(|f| f.parse::<u64>().ok());
+        let (Some(length), Some(offset), Some(line_bases), Some(line_width)) = (number(1), number(2), number(3), number(4))
+        else {
+            bail!("Malformed fasta index {path}: {line}");
+        };
+        entries.push(Entry { name: fields[0].to_string(), length, offset, line_bases, line_width });
+    }
+    Ok(entries)
+}
+
+fn write_fai(path: &str, entries: &[Entry]) -> io::Result<()> {
+    let mut file = io::BufWriter::new(File::create(path)?);
+    for e in entries {
+        writeln!(file, "{}\t{}\t{}\t{}\t{}", e.name, e.length, e.offset, e.line_bases, e.line_width)?;
+    }
+    file.flush()
+}
+
+// Index uncompressed fasta text. Lines of a record must have equal length, except the last.
+fn index_fasta(reader: impl Read, path: &str) -> Result<Vec<Entry>> {
+    let mut reader = BufReader::new(reader);
+    let mut entries: Vec<Entry> = vec![];
+    let mut line = vec![];
+    let mut offset = 0;
+    // Whether a shorter line has ended the lines of equal length of the current record.
+    let mut short = false;
+    loop {
+        line.clear();
+        let width = reader.read_until(b'\n', &mut line)? as u64;
+        if width == 0 {
+            break;
+        }
+        offset += width;
+        let text = line.strip_suffix(b"\n").unwrap_or(&line);
+        let text = text.strip_suffix(b"\r").unwrap_or(text);
+        if let Some(header) = text.strip_prefix(b">") {
+            let header = String::from_utf8_lossy(header);
+            let name = header.split_whitespace().next().unwrap_or_default().to_string();
+            entries.push(Entry { name, length: 0, offset, line_bases: 0, line_width: 0 });
+            short = false;
+            continue;
+        }
+        let Some(entry) = entries.last_mut() else {
+            bail!("Not a fasta file: {path}");
+        };
+        let bases = text.len() as u64;
+        if bases == 0 {
+            short = true;
+            continue;
+        }
+        if entry.line_bases == 0 {
+            entry.line_bases = bases;
+            entry.line_width = width;
+        } else if short || bases > entry.line_bases {
+            bail!("Different line lengths in record '{}' of {path}, which can't be indexed.", entry.name);
+        }
+        short |= bases < entry.line_bases;
+        entry.length += bases;
+    }
+    Ok(entries)
+}
+
+// Whether a file starts with a BGZF block, i.e. a gzip member with a "BC" extra subfield.
+fn is_bgzf(file: &mut File) -> io::Result<bool> {
+    let mut header = [0; 16];
+    let n = file.read(&mut header)?;
+    file.rewind()?;
+    Ok(n == 16 && header[..4] == [0x1f, 0x8b, 8, 4] && header[12..14] == *b"BC")
+}
+
+fn is_gzip(file: &mut File) -> io::Result<bool> {
+    let mut magic = [0; 2];
+    let n = file.read(&mut magic)?;
+    file.rewind()?;
+    Ok(n == 2 && magic == [0x1f, 0x8b])
+}
+
+// (compressed, uncompressed) offsets of the start of each BGZF block but the first, as in .gzi files.
+fn index_bgzf(file: &mut File) -> Result<Vec<(u64, u64)>> {
+    let size = file.metadata()?.len();
+    let mut blocks = vec![];
+    let (mut coffset, mut uoffset) = (0, 0);
+    let mut header = [0; 18];
+    let mut isize = [0; 4];
+    while coffset < size {
+        file.seek(SeekFrom::Start(coffset))?;
+        file.read_exact(&mut header)?;
+        if header[..4] != [0x1f, 0x8b, 8, 4] || header[12..14] != *b"BC" {
+            bail!("Not a BGZF block at byte {coffset}.");
+        }
+        let block_size = u16::from_le_bytes([header[16], header[17]]) as u64 + 1;
+        file.seek(SeekFrom::Start(coffset + block_size - 4))?;
+        file.read_exact(&mut isize)?;
+        if coffset > 0 {
+            blocks.push((coffset, uoffset));
+        }
+        coffset += block_size;
+        uoffset += u32::from_le_bytes(isize) as u64;
+    }
+    file.rewind()?;
+    Ok(blocks)
+}
+
+fn read_gzi(path: &str) -> Result<Vec<(u64, u64)>> {
+    let mut bytes = vec![];
+    File::open(path)?.read_to_end(&mut bytes)?;
+    let numbers: Vec<u64> = bytes.chunks_exact(8).map(|b| u64::from_le_bytes(b.try_into().unwrap())).collect();
+    match numbers.split_first() {
+        Some((&n, pairs)) if pairs.len() as u64 == 2 * n => Ok(pairs.chunks(2).map(|p| (p[0], p[1])).collect()),
+        _ => bail!("Malformed BGZF index: {path}"),
+    }
+}
+
+fn write_gzi(path: &str, blocks: &[(u64, u64)]) -> io::Result<()> {
+    let mut file = io::BufWriter::new(File::create(path)?);
+    file.write_all(&(blocks.len() as u64).to_le_bytes())?;
+    for (coffset, uoffset) in blocks {
+        file.write_all(&coffset.to_le_bytes())?;
+        file.write_all(&uoffset.to_le_bytes())?;
+    }
+    file.flush()
+}
+
+// Indexes are written next to the fasta file, or only kept in memory if that fails.
+fn save(path: &str, result: io::Result<()>) {
+    if let Err(e) = result {
+        eprintln!("Could not write index {path}: {e}");
+    }
+}
+
+pub struct Faidx {
+    path: String,
+    entries: Vec<Entry>,
+    // Block offsets if BGZF compressed.
+    blocks: Option<Vec<(u64, u64)>>,
+}
+
+impl Faidx {
+    // Read the .fai (and .gzi) index of a fasta file, creating them if missing.
+    pub fn open(path: &str) -> Result<Self> {
+        let mut file = File::open(path).map_err(|e| anyhow::anyhow!("{path}: {e}"))?;
+        let blocks = if is_bgzf(&mut file)? {
+            let gzi = format!("{path}.gzi");
+            Some(match read_gzi(&gzi) {
+                Ok(blocks) => blocks,
+                Err(_) => {
+                    let blocks = index_bgzf(&mut file)?;
+                    save(&gzi, write_gzi(&gzi, &blocks));
+                    blocks
+                }
+            })
+        } else if is_gzip(&mut file)? {
+            bail!("{path} is gzip but not BGZF compressed, so it can't be indexed. Recompress it with bgzip.");
+        } else {
+            None
+        };
+        let fai = format!("{path}.fai");
+        let entries = match read_fai(&fai) {
+            Ok(entries) => entries,
+            Err(_) => {
+                let entries = match blocks {
+                    Some(_) => index_fasta(MultiGzDecoder::new(file), path)?,
+                    None => index_fasta(file, path)?,
+                };
+                save(&fai, write_fai(&fai, &entries));
+                entries
+            }
+        };
+        Ok(Faidx { path: path.to_string(), entries, blocks })
+    }
+
+    pub fn contains(&self, name: &str) -> bool {
+        self.entries.iter().any(|e| e.name == name)
+    }
+
+    // Uncompressed bytes from an offset.
+    fn read_at(&self, offset: u64) -> Result<Box<dyn Read>> {
+        let mut file = File::open(&self.path)?;
+        match &self.blocks {
+            None => {
+                file.seek(SeekFrom::Start(offset))?;
+                Ok(Box::new(file))
+            }
+            Some(blocks) => {
+                // Decompress from the last block starting at or before the offset.
+                let i = blocks.partition_point(|&(_, uoffset)| uoffset <= offset);
+                let (coffset, uoffset) = if i == 0 { (0, 0) } else { blocks[i - 1] };
+                file.seek(SeekFrom::Start(coffset))?;
+                let mut reader = MultiGzDecoder::new(BufReader::new(file));
+                io::copy(&mut (&mut reader).take(offset - uoffset), &mut io::sink())?;
+                Ok(Box::new(reader))
+            }
+        }
+    }
+
+    // Fasta lines of a region, with the region in the header and lines wrapped as in the file.
+    pub fn fetch(&self, region: &Region) -> Result<Vec<String>> {
+        let Some(entry) = self.entries.iter().find(|e| e.name == region.name) else {
+            bail!("Sequence '{}' not found in {}.", region.name, self.path);
+        };
+        let start = region.start as u64;
+        let end = region.end.map_or(entry.length, |end| (end as u64).min(entry.length));
+        if start >= entry.length {
+            bail!("Region {region} is beyond the length {} of '{}'.", entry.length, entry.name);
+        }
+        let first = entry.offset(start);
+        let last = entry.offset(end - 1);
+        let mut bytes = vec![];
+        self.read_at(first)?.take(last + 1 - first).read_to_end(&mut bytes)?;
+        bytes.retain(|&b| b != b'\n' && b != b'\r');
+        let seq = String::from_utf8_lossy(&bytes);
+        let mut lines = vec![format!(">{}:{}-{end}", entry.name, start + 1)];
+        let seq: Vec<char> = seq.chars().collect();
+        lines.extend(seq.chunks(entry.line_bases.max(1) as usize).map(|chunk| chunk.iter().collect()));
+        Ok(lines)
+    }
+}
@@ -0,0 +1,86 @@
+// Assignment of records to groups, e.g. clades, for a consensus per group.
+use anyhow::Result;
+use regex::Regex;
+use std::collections::HashMap;
+use std::fs;
+
+use crate::ansi_colors::Char;
+use crate::consensus::Column;
+use crate::records::Record;
+use crate::render::Line;
+
+// Groups by a header regex or an ID to group mapping. Without either all records are one group.
+pub struct Groups {
+    regex: Option<Regex>,
+    mapping: Option<HashMap<String, String>>,
+    // Group names in order of first appearance, which index groups.
+    names: Vec<String>,
+}
+
+impl Groups {
+    pub fn new(regex: Option<&str>, file: Option<&str>) -> Result<Self> {
+        let regex = regex.map(Regex::new).transpose()?;
+        let mapping = match file {
+            None => None,
+            Some(path) => {
+                let text = fs::read_to_string(path).map_err(|e| anyhow::anyhow!("{path}: {e}"))?;
+                let mut mapping = HashMap::new();
+                for line in text.lines().filter(|line| !line.trim().is_empty() && !line.starts_with('#')) {
+                    match line.split_once('\t') {
+                        Some((id, group)) => mapping.insert(id.trim().to_string(), group.trim().to_string()),
+                        None => anyhow::bail!("Group file {path} needs an ID and a group separated by tab: {line}"),
+                    };
+                }
+                Some(mapping)
+            }
+        };
+        Ok(Groups { regex, mapping, names: vec![] })
+    }
+
+    pub fn is_grouped(&self) -> bool {
+        self.regex.is_some() || self.mapping.is_some()
+    }
+
+    // Index of the group of a record. The group is the first capture group of the regex if any,
+    // otherwise the whole match. Records without a group are grouped together.
+    pub fn group(&mut self, record: &Record<String>) -> usize {
+        let name = if let Some(re) = &self.regex {
+            record.header.as_ref().and_then(|header| re.captures(header)).map(|caps| {
+                caps.get(1).or_else(|| caps.get(0)).map_or("", |m| m.as_str()).to_string()
+            })
+        } else if let Some(mapping) = &self.mapping {
+            record.id().and_then(|id| mapping.get(id)).cloned()
+        } else {
+            None
+        };
+        let name = name.unwrap_or_default();
+        match self.names.iter().position(|n| *n == name) {
+            Some(i) => i,
+            None => {
+                self.names.push(name);
+                self.names.len() - 1
+            }
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.names.len()
+    }
+}
+
+// A row marking columns with "*" where the consensus of at least two groups differ.
+pub fn differing(consensuses: &[Vec<Option<Column>>]) -> Line {
+    let n = consensuses.iter().map(|consensus| consensus.len()).max().unwrap_or(0);
+    let mut chars: Vec<Char> = (0..n)
+        .map(|i| {
+            let mut letters = consensuses.iter().filter_map(|consensus| consensus.get(i).and_then(|column| column.as_ref()).map(|column| column.char));
+            let first = letters.next();
+            let differ = first.is_some() && letters.any(|c| Some(c) != first);
+            Char::Unstyled(if differ { '*' } else { ' ' })
+        })
+        .collect();
+    while matches!(chars.last(), Some(Char::Unstyled(' '))) {
+        chars.pop();
+    }
+    Line::new(chars)
+}
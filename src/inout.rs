@@ -1,6 +1,13 @@
 use anyhow::Result;
+use std::cell::RefCell;
+use std::collections::VecDeque;
 use std::fs::File;
-use std::io::{self, BufRead, BufReader};
+use std::io::{self, BufRead, BufReader, Cursor, Read};
+use std::rc::Rc;
+use std::sync::mpsc::{Receiver, sync_channel};
+use std::thread;
+
+use crate::ansi_colors::strip_ansi;
 
 // Understand "-" to mean stdin.
 pub fn open(filename: &str) -> Result<Box<dyn BufRead>> {
@@ -10,28 +17,273 @@ pub fn open(filename: &str) -> Result<Box<dyn BufRead>> {
     }
 }
 
-// Iterator of lines from file(s).
-pub fn read_lines(filenames: Vec<String>) -> Result<impl Iterator<Item=String>> {
-    let mut iters = vec![];
-    for filename in filenames {
-        match open(&filename) {
-            Err(e) => eprintln!("{filename}: {e}"),
-            Ok(file) => {
-                iters.push(file.lines().map(Result::unwrap));
+// Open a file or stdin for reading, or print why it can't be read.
+fn open_file(filename: &str) -> Option<Box<dyn Read + Send>> {
+    let file: io::Result<Box<dyn Read + Send>> = match filename {
+        "-" => Ok(Box::new(io::stdin())),
+        _ => File::open(filename).map(|f| Box::new(f) as Box<dyn Read + Send>),
+    };
+    file.map_err(|e| eprintln!("{filename}: {e}")).ok()
+}
+
+// What a prefetch thread sends for its file: blocks, then the end of the file or an error.
+enum Block {
+    Data(Vec<u8>),
+    Error(io::Error),
+    End,
+}
+
+// Read a file in blocks in a background thread. A file that can't be opened is empty, after
+// printing why.
+fn read_ahead(filename: String) -> Receiver<Block> {
+    let (tx, rx) = sync_channel(16);
+    thread::spawn(move || {
+        if let Some(mut file) = open_file(&filename) {
+            loop {
+                let mut block = vec![0; 1 << 16];
+                match file.read(&mut block) {
+                    Ok(0) => break,
+                    Ok(n) => {
+                        block.truncate(n);
+                        if tx.send(Block::Data(block)).is_err() {
+                            return;
+                        }
+                    }
+                    Err(e) if e.kind() == io::ErrorKind::Interrupted => {}
+                    Err(e) => {
+                        let _ = tx.send(Block::Error(e));
+                        break;
+                    }
+                }
+            }
+        }
+        let _ = tx.send(Block::End);
+    });
+    rx
+}
+
+// Blocks of files read ahead concurrently in background threads, a thread per file for up to a
+// given number of files at a time, so reading overlaps with coloring and slow files, e.g. pipes,
+// don't hold up the next ones. Files are opened only when their thread starts and each channel is
+// bounded, so a fast reader holds at most about 1 MB of input per file ahead of the coloring.
+// Files are still read out in the given order.
+struct Prefetch {
+    // Files being read, in order, and files not started yet.
+    reading: VecDeque<Receiver<Block>>,
+    waiting: VecDeque<String>,
+    // Blocks of the current file received but not yet read, and the read position in the first.
+    queue: VecDeque<Block>,
+    pos: usize,
+}
+
+impl Prefetch {
+    fn spawn(filenames: Vec<String>, concurrent: usize) -> Self {
+        let mut waiting = VecDeque::from(filenames);
+        let n = concurrent.min(waiting.len());
+        let reading = waiting.drain(..n).map(read_ahead).collect();
+        Prefetch { reading, waiting, queue: VecDeque::new(), pos: 0 }
+    }
+
+    // Number of bytes of the current file that have been read ahead.
+    fn available(&mut self) -> usize {
+        if let Some(blocks) = self.reading.front() {
+            while let Ok(block) = blocks.try_recv() {
+                self.queue.push_back(block);
+            }
+        }
+        let queued: usize = self
+            .queue
+            .iter()
+            .map_while(|block| match block {
+                Block::Data(data) => Some(data.len()),
+                _ => None,
+            })
+            .sum();
+        queued.saturating_sub(self.pos)
+    }
+
+    // Read from the current file, where Ok(0) ends it and moves on to the next file.
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        if self.queue.is_empty() {
+            let Some(blocks) = self.reading.front() else {
+                return Ok(0);
+            };
+            // A thread that stopped without an end can only have panicked, which ends its file.
+            self.queue.push_back(blocks.recv().unwrap_or(Block::End));
+        }
+        let Some(Block::Data(data)) = self.queue.front() else {
+            // The end of the file, or an error which is followed by the end.
+            return match self.queue.pop_front() {
+                Some(Block::Error(e)) => Err(e),
+                _ => {
+                    // Start reading the next file in place of this one.
+                    self.reading.pop_front();
+                    self.reading.extend(self.waiting.pop_front().map(read_ahead));
+                    Ok(0)
+                }
+            };
+        };
+        let n = buf.len().min(data.len() - self.pos);
+        buf[..n].copy_from_slice(&data[self.pos..self.pos + n]);
+        self.pos += n;
+        if self.pos == data.len() {
+            self.queue.pop_front();
+            self.pos = 0;
+        }
+        Ok(n)
+    }
+}
+
+enum Input {
+    // A file not opened until it's read.
+    Unopened(String),
+    Direct(Box<dyn Read>),
+    // A file read ahead by the prefetch shared by all files.
+    Prefetch(Rc<RefCell<Prefetch>>),
+}
+
+impl Read for Input {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        match self {
+            Input::Unopened(filename) => match open_file(filename) {
+                Some(file) => {
+                    *self = Input::Direct(file);
+                    self.read(buf)
+                }
+                None => {
+                    *self = Input::Direct(Box::new(io::empty()));
+                    Ok(0)
+                }
+            },
+            Input::Direct(file) => file.read(buf),
+            Input::Prefetch(prefetch) => prefetch.borrow_mut().read(buf),
+        }
+    }
+}
+
+// Lines from file(s) without line endings. Can be iterated as strings, or read into a reused
+// buffer for speed.
+pub struct Lines {
+    readers: VecDeque<BufReader<Input>>,
+    peeked: Option<Vec<u8>>,
+    // Remove ANSI escape sequences from lines.
+    strip_ansi: bool,
+}
+
+impl Lines {
+    // Read files one after the other, opening each when reached, or with more than one thread
+    // read up to that many files ahead concurrently.
+    pub fn open(filenames: Vec<String>, threads: usize) -> Self {
+        let inputs: Vec<Input> = match threads {
+            0 | 1 => filenames.into_iter().map(Input::Unopened).collect(),
+            _ => {
+                let n = filenames.len();
+                let prefetch = Rc::new(RefCell::new(Prefetch::spawn(filenames, threads)));
+                (0..n).map(|_| Input::Prefetch(prefetch.clone())).collect()
+            }
+        };
+        let readers = inputs.into_iter().map(|input| BufReader::with_capacity(1 << 16, input)).collect();
+        Lines { readers, peeked: None, strip_ansi: false }
+    }
+
+    // Lines held in memory, e.g. converted from another format.
+    pub fn from_lines(lines: impl IntoIterator<Item = String>) -> Self {
+        let mut text = String::new();
+        for line in lines {
+            text.push_str(&line);
+            text.push('\n');
+        }
+        let reader = Input::Direct(Box::new(Cursor::new(text.into_bytes())));
+        Lines { readers: VecDeque::from([BufReader::new(reader)]), peeked: None, strip_ansi: false }
+    }
+
+    // Lines of an open file, e.g. a temporary file spooled from stdin.
+    pub fn from_reader(reader: impl Read + 'static) -> Self {
+        let reader = Input::Direct(Box::new(reader));
+        Lines { readers: VecDeque::from([BufReader::with_capacity(1 << 16, reader)]), peeked: None, strip_ansi: false }
+    }
+
+    pub fn strip_ansi(mut self, strip: bool) -> Self {
+        self.strip_ansi = strip;
+        self
+    }
+
+    // Read the next line into buf, replacing its content. Returns false when there are no more lines.
+    pub fn read_into(&mut self, buf: &mut Vec<u8>) -> io::Result<bool> {
+        if let Some(peeked) = self.peeked.take() {
+            *buf = peeked;
+            return Ok(true);
+        }
+        buf.clear();
+        while let Some(reader) = self.readers.front_mut() {
+            if reader.read_until(b'\n', buf)? > 0 {
+                if buf.last() == Some(&b'\n') {
+                    buf.pop();
+                    if buf.last() == Some(&b'\r') {
+                        buf.pop();
+                    }
+                }
+                if self.strip_ansi && buf.contains(&0x1B) {
+                    *buf = strip_ansi(&String::from_utf8_lossy(buf)).into_bytes();
+                }
+                return Ok(true);
             }
+            self.readers.pop_front();
         }
+        Ok(false)
+    }
+
+    // Number of bytes that can be read without blocking on the input.
+    pub fn buffered(&mut self) -> usize {
+        match self.readers.front_mut() {
+            None => 0,
+            Some(reader) => {
+                let buffered = reader.buffer().len();
+                match reader.get_mut() {
+                    Input::Prefetch(prefetch) => buffered + prefetch.borrow_mut().available(),
+                    _ => buffered,
+                }
+            }
+        }
+    }
+
+    // The lines of each file separately, e.g. to parse each file as a whole. Files already read
+    // to the end are left out.
+    pub fn files(self) -> Vec<Lines> {
+        let mut peeked = self.peeked;
+        let strip_ansi = self.strip_ansi;
+        self.readers
+            .into_iter()
+            .map(|reader| Lines { readers: VecDeque::from([reader]), peeked: peeked.take(), strip_ansi })
+            .collect()
+    }
+
+    // The next line without consuming it, or an empty string at the end.
+    pub fn peek(&mut self) -> String {
+        if self.peeked.is_none() {
+            let mut buf = vec![];
+            if self.read_into(&mut buf).unwrap_or(false) {
+                self.peeked = Some(buf);
+            }
+        }
+        self.peeked.as_ref().map_or(String::new(), |line| String::from_utf8_lossy(line).into_owned())
     }
-    Ok(iters.into_iter().flatten())
 }
 
+impl Iterator for Lines {
+    type Item = String;
 
-// Read lines along with a number of maximum line length.
-pub fn read_lines_max(filenames: Vec<String>) -> Result<(Vec<String>, usize)> {
-    let mut lines = Vec::new();
-    let mut max_line = 0;
-    for line in read_lines(filenames)? {
-        max_line = max_line.max(line.len());
-        lines.push(line);
+    fn next(&mut self) -> Option<String> {
+        let mut buf = vec![];
+        match self.read_into(&mut buf) {
+            Ok(true) => Some(String::from_utf8(buf).unwrap_or_else(|e| String::from_utf8_lossy(e.as_bytes()).into_owned())),
+            Ok(false) => None,
+            Err(e) => panic!("{e}"),
+        }
     }
-    Ok((lines, max_line))
+}
+
+// Iterator of lines from file(s).
+pub fn read_lines(filenames: Vec<String>, threads: usize) -> Result<Lines> {
+    Ok(Lines::open(filenames, threads))
 }
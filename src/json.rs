@@ -0,0 +1,158 @@
+// Coloring string values of selected fields of JSON and JSON Lines, keeping everything else as is
+// or pretty-printed.
+use crate::ansi_colors::Char;
+
+enum Container {
+    // The last key seen and whether a key is expected next.
+    Object { key: String, expect_key: bool },
+    Array,
+}
+
+// Scanner state kept between lines, so a document may span lines or there may be a document per
+// line. Strings can't span lines in JSON.
+pub struct Json {
+    // Key paths of fields to color, where "*" matches any key, e.g. "chains.*.seq".
+    paths: Vec<Vec<String>>,
+    pretty: bool,
+    stack: Vec<Container>,
+    // Whether a container was just opened, so it may be closed right away without a newline.
+    opened: bool,
+}
+
+impl Json {
+    pub fn new(paths: &[String], pretty: bool) -> Self {
+        let paths = paths.iter().map(|p| p.split('.').map(String::from).collect()).collect();
+        Json { paths, pretty, stack: vec![], opened: false }
+    }
+
+    // Whether the value at the current position is of a selected field. Arrays are transparent.
+    fn selected(&self) -> bool {
+        let keys: Vec<&str> = self
+            .stack
+            .iter()
+            .filter_map(|c| match c {
+                Container::Object { key, .. } => Some(key.as_str()),
+                Container::Array => None,
+            })
+            .collect();
+        self.paths.iter().any(|path| {
+            path.len() == keys.len() && path.iter().zip(&keys).all(|(p, k)| p == "*" || p == k)
+        })
+    }
+
+    fn newline(&self, chars: &mut Vec<Char>) {
+        chars.push(Char::Unstyled('\n'));
+        chars.extend((0..2 * self.stack.len()).map(|_| Char::Unstyled(' ')));
+    }
+
+    // Start a new token when pretty-printing, breaking the line after an opened container.
+    fn token(&mut self, chars: &mut Vec<Char>) {
+        if self.pretty && self.opened {
+            self.newline(chars);
+        }
+        self.opened = false;
+    }
+
+    // Chars of a line with selected string values painted. When pretty-printing, whitespace is
+    // replaced and lines are broken after each opening bracket and comma, and after each document.
+    pub fn line(&mut self, line: &str, paint: impl Fn(&str) -> Vec<Char>) -> Vec<Char> {
+        let mut chars = vec![];
+        let mut iter = line.char_indices().peekable();
+        while let Some((i, c)) = iter.next() {
+            match c {
+                c if c.is_whitespace() => {
+                    if !self.pretty {
+                        chars.push(Char::Unstyled(c));
+                    }
+                }
+                '{' | '[' => {
+                    self.token(&mut chars);
+                    chars.push(Char::Unstyled(c));
+                    self.stack.push(match c {
+                        '{' => Container::Object { key: String::new(), expect_key: true },
+                        _ => Container::Array,
+                    });
+                    self.opened = true;
+                }
+                '}' | ']' => {
+                    self.stack.pop();
+                    if self.pretty && !self.opened {
+                        self.newline(&mut chars);
+                    }
+                    self.opened = false;
+                    chars.push(Char::Unstyled(c));
+                    if self.pretty && self.stack.is_empty() {
+                        chars.push(Char::Unstyled('\n'));
+                    }
+                }
+                ',' => {
+                    chars.push(Char::Unstyled(c));
+                    if let Some(Container::Object { expect_key, .. }) = self.stack.last_mut() {
+                        *expect_key = true;
+                    }
+                    if self.pretty {
+                        self.newline(&mut chars);
+                    }
+                }
+                ':' => {
+                    chars.push(Char::Unstyled(c));
+                    if self.pretty {
+                        chars.push(Char::Unstyled(' '));
+                    }
+                }
+                '"' => {
+                    self.token(&mut chars);
+                    // Find the closing quote, skipping escaped chars.
+                    let start = i + 1;
+                    let mut end = line.len();
+                    while let Some((j, d)) = iter.next() {
+                        match d {
+                            '\\' => {
+                                iter.next();
+                            }
+                            '"' => {
+                                end = j;
+                                break;
+                            }
+                            _ => {}
+                        }
+                    }
+                    let text = &line[start..end];
+                    let is_key = match self.stack.last_mut() {
+                        Some(Container::Object { key, expect_key }) if *expect_key => {
+                            *key = text.to_string();
+                            *expect_key = false;
+                            true
+                        }
+                        _ => false,
+                    };
+                    chars.push(Char::Unstyled('"'));
+                    if !is_key && self.selected() {
+                        chars.extend(paint(text));
+                    } else {
+                        chars.extend(text.chars().map(Char::Unstyled));
+                    }
+                    if end < line.len() {
+                        chars.push(Char::Unstyled('"'));
+                    }
+                }
+                c => {
+                    // Numbers, true, false, and null.
+                    self.token(&mut chars);
+                    chars.push(Char::Unstyled(c));
+                    while let Some(&(_, d)) = iter.peek() {
+                        if d.is_whitespace() || ",:]}".contains(d) {
+                            break;
+                        }
+                        chars.push(Char::Unstyled(d));
+                        iter.next();
+                    }
+                    if self.pretty && self.stack.is_empty() {
+                        chars.push(Char::Unstyled('\n'));
+                    }
+                }
+            }
+        }
+        chars
+    }
+}
@@ -3,28 +3,68 @@
 use anyhow::Result;
 use clap::Parser;
 use regex::Regex;
+use std::cell::Cell;
 use std::collections::HashSet;
 use std::fs::File;
-use std::io::{self, IsTerminal, Read, Write};
+use std::io::{self, BufWriter, IsTerminal, Read, Seek, Write};
 use std::process::{exit, Child, Command, Stdio};
 use std::{collections::HashMap, env, vec};
 
-// For detecting if terminal can show true colors, etc.
-use anstyle_query;
 // For abstracting away writing ANSI codes.
 use yansi::Color::{self, *};
 
+mod align;
+mod annotation;
 mod ansi_colors;
 mod bio;
+mod codon;
 mod colorschemes;
+mod consensus;
+mod diff;
+mod faidx;
+mod groups;
 mod inout;
+mod json;
+mod msa;
+mod pdb;
+mod phylo;
+mod pileup;
+mod qc;
+mod records;
+mod render;
+mod repeats;
+mod select;
+mod sort;
+mod structure;
+mod table;
+mod transform;
+mod variants;
 
-use crate::ansi_colors::ansi_byte;
-use crate::inout::read_lines;
-use crate::{
-    ansi_colors::{Char, ansi256, is_light, to_painted, write_ansi},
-    colorschemes::parse_color,
+use crate::align::{Mode, Scoring};
+use crate::annotation::{
+    AnnotationMode, AnnotationOpts, Feature, Features, annotate, read_bed, read_feature_colors, read_flat_file_features, read_gff,
 };
+use crate::ansi_colors::{Case, Char, Highlight, StyleTable, ansi256, ansi_byte, is_light, write_ansi};
+use crate::bio::Region;
+use crate::codon::{CodonOpts, Frame, GENETIC_CODES, GeneticCode, paint_codons};
+use crate::consensus::{Column, ConsensusCase, ConsensusOpts, Counter, Counts, Ties, highlight};
+use crate::diff::{DiffOpts, read_sequences, write_diff};
+use crate::faidx::Faidx;
+use crate::groups::{Groups, differing};
+use crate::inout::{Lines, read_lines};
+use crate::json::Json;
+use crate::pdb::{BfactorOpts, Scale, Source, paint_bfactors, read_chains};
+use crate::pileup::{PileupOpts, pileup};
+use crate::qc::{Check, QcOpts, format_summary, qc};
+use crate::records::{Record, is_flat_file_header, records};
+use crate::render::{Line, paint, write_transposed};
+use crate::repeats::{Repeat, RepeatOpts, paint_repeats};
+use crate::select::Selection;
+use crate::sort::{SortKey, sort};
+use crate::structure::{StructureOpts, Structures, paint_structure, read_structure};
+use crate::table::{Table, widths};
+use crate::transform::{Transform, parse_columns, slice, transform};
+use crate::variants::{VariantOpts, paint_variants, read_vcf};
 
 /// Pager mode configuration.
 enum PagingMode {
@@ -109,7 +149,11 @@ struct Args {
     #[arg(
         value_name = "FILE",
         default_value = "-",
-        help = "Text containing sequences. Default is reading stdin."
+        help = "Text containing sequences, e.g. fasta, fastq, GenBank, or EMBL. \
+        PDB and mmCIF files (.pdb/.ent or .cif/.mmcif, or given --from) are read as a fasta record for each chain, \
+        and PHYLIP (relaxed or strict, sequential or interleaved) and NEXUS matrices as a fasta record for each taxon, \
+        when named .phy/.phylip or .nex/.nexus/.nxs or given --from. \
+        Default is reading stdin."
     )]
     files: Vec<String>,
 
@@ -171,6 +215,19 @@ struct Args {
     )]
     invisible: Option<String>,
 
+    #[arg(
+        long,
+        value_name("MODE"),
+        default_value = "sensitive",
+        help = "How to color lowercase chars, e.g. soft-masked (repeat-masked) bases or A2M insert states. \
+        \"sensitive\": only color lowercase chars defined in the colorschemes. \
+        \"insensitive\": color lowercase chars like their uppercase. \
+        \"dim\", \"italic\", \"underline\", etc. or a background color: color like uppercase and mark lowercase chars with the style. \
+        \"hide\": remove lowercase chars from sequences, e.g. to only show match states of A2M. \
+        GenBank and EMBL sequences are lowercase by convention and are colored like uppercase except with \"hide\"."
+    )]
+    case: String,
+
     // Options controlling what to color.
     #[arg(
         short('m'),
@@ -199,10 +256,12 @@ struct Args {
         short('c'),
         long("consensus"),
         value_name("STYLE"),
-        help = "Compute the consensus sequence and indicate it in each sequence by \"bold\", \"underline\", or a color. \
-        Ties: no letter is highlighted. \
+        help = "Compute the consensus sequence and indicate it in each sequence by \"bold\", \"dim\", \"italic\", \"underline\", \"invert\", \"strike\", or a background color. \
+        Ties: no letter is highlighted, unless set by --consensus-ties. \
         Affected by options -r/--regex, -m/--min, and -a/--alphabet. \
-        Non-streaming."
+        Reads the input twice, first counting columns, then coloring one record at a time. \
+        Stdin is spooled to a temporary file. \
+        Non-streaming with -T/--transpose or tables."
     )]
     consensus: Option<String>,
 
@@ -213,10 +272,79 @@ struct Args {
         help = "Opposite of -c/--consensus. \
         Highlight mutations/deviations from consensus. \
         Affected by options -r/--regex, -m/--min, and -a/--alphabet. \
-        Non-streaming."
+        Reads the input twice like -c/--consensus."
     )]
     mutations: Option<String>,
 
+    #[arg(
+        long,
+        value_name("MODE"),
+        default_value = "sensitive",
+        help = "How lowercase chars are treated for -c/--consensus and -C/--mut. \
+        \"sensitive\": lowercase and uppercase are different chars. \
+        \"ignore\": lowercase chars are not counted nor highlighted. \
+        \"fold\": lowercase chars are counted as uppercase."
+    )]
+    consensus_case: String,
+
+    #[arg(
+        long,
+        value_name("FRACTION"),
+        default_value_t = 0.0,
+        help = "Min fraction of the counted chars of a column for a consensus for -c/--consensus and -C/--mut, \
+        e.g. 0.5 for a majority. Columns below are not highlighted."
+    )]
+    consensus_threshold: f64,
+
+    #[arg(
+        long,
+        help = "Don't count gaps (\"-\" and \".\") for -c/--consensus and -C/--mut, \
+        so they are neither consensus nor part of the total for --consensus-threshold, and aren't highlighted."
+    )]
+    exclude_gaps: bool,
+
+    #[arg(
+        long,
+        value_name("MODE"),
+        default_value = "none",
+        help = "How to break ties between chars with the max count for -c/--consensus and -C/--mut. \
+        \"none\": no consensus. \
+        \"alphabetical\": the first char alphabetically. \
+        \"iupac\": the IUPAC ambiguity code of tied nucleotides, where each tied nucleotide matches the consensus. \
+        Ties of other chars give no consensus."
+    )]
+    consensus_ties: String,
+
+    #[arg(
+        long,
+        value_name("METHOD"),
+        default_value = "none",
+        help = "Weight sequences when counting for -c/--consensus and -C/--mut. \
+        \"none\": equal weights. \
+        \"henikoff\": Henikoff position-based weights, which down-weight redundant sequences. \
+        Weights are computed within each group of --group-regex or --group-file. \
+        Reads the input an extra time."
+    )]
+    weights: String,
+
+    #[arg(
+        long,
+        value_name("REGEX"),
+        help = "Group records by a regex on their header, e.g. \"\\[(.*)\\]\" for the text in square brackets, \
+        where the group is the first capture group or otherwise the whole match. \
+        -c/--consensus and -C/--mut highlight each record relative to the consensus of its own group, \
+        and columns where the consensus of groups differ are marked with \"*\" in a row after the records. \
+        Records without a group are grouped together."
+    )]
+    group_regex: Option<String>,
+
+    #[arg(
+        long,
+        value_name("FILE"),
+        help = "Group records by a TSV file of record IDs and group names. See --group-regex."
+    )]
+    group_file: Option<String>,
+
     #[arg(
         short('T'),
         long,
@@ -226,6 +354,504 @@ struct Args {
     )]
     transpose: bool,
 
+    #[arg(
+        short('t'),
+        long("transform"),
+        value_name("TRANSFORM"),
+        value_delimiter(','),
+        help = "Transform sequences before coloring. \
+        \"revcomp\" for reverse complement, \"comp\" for complement, \"rev\" for reverse, \
+        \"rna\" to convert T to U, and \"dna\" to convert U to T. \
+        Complement is IUPAC aware, gives U for A in RNA, and gaps are kept. \
+        Applied to whole fasta and fastq records where reversing also reverses qualities, \
+        while lines outside records are left unchanged. \
+        Header lines are left unchanged except for --suffix. \
+        Can be comma separated or given multiple times to apply in order."
+    )]
+    transforms: Vec<String>,
+
+    #[arg(
+        long,
+        value_name("SUFFIX"),
+        help = "Append to header lines of records transformed by -t/--transform, e.g. \"/rc\"."
+    )]
+    suffix: Option<String>,
+
+    #[arg(
+        long,
+        value_name("RANGE"),
+        help = "Only keep columns START-END, START-, or -END of each sequence with 1-based inclusive coordinates, \
+        e.g. to slice an alignment. \
+        Wrapped sequences are joined onto a single line. \
+        Applied before -t/--transform."
+    )]
+    columns: Option<String>,
+
+    #[arg(
+        long,
+        value_name("FORMAT"),
+        help = "Read input in the given format rather than guessing it from file extensions, e.g. when reading stdin. \
+        \"pdb\", \"mmcif\", \"phylip\" (relaxed or strict), \"nexus\", or \"fasta\", which covers any other text."
+    )]
+    from: Option<String>,
+
+    #[arg(
+        long,
+        value_name("FORMAT"),
+        help = "Write sequences without color in the given format instead of coloring them. \
+        \"fasta\", \"phylip\" (relaxed), \"phylip-strict\" (names truncated to 10 chars), or \"nexus\". \
+        Sequences are written after --columns and -t/--transform, \
+        and must have equal length for PHYLIP and NEXUS. \
+        Non-streaming."
+    )]
+    to: Option<String>,
+
+    // Record selection.
+    #[arg(
+        long,
+        value_name("IDS"),
+        value_delimiter(','),
+        help = "Only show records with these IDs, i.e. the first word of the header. \
+        Can be comma separated or given multiple times. \
+        Record filters are combined so a record has to match all of them. \
+        Records are selected before -c/--consensus, see --consensus-all."
+    )]
+    select: Vec<String>,
+
+    #[arg(
+        long,
+        value_name("FILE"),
+        help = "Only show records with IDs listed in a file, one per line. Combined with --select."
+    )]
+    select_file: Option<String>,
+
+    #[arg(
+        long,
+        value_name("REGEX"),
+        help = "Only show records with a header matching a regex, e.g. \"Homo sapiens\"."
+    )]
+    grep_header: Option<String>,
+
+    #[arg(
+        long,
+        value_name("MOTIF"),
+        help = "Only show records with a sequence containing a motif (case-insensitive regex), e.g. \"GAATTC\" or \"N[^P][ST]\". \
+        The sequence is searched unwrapped and without gaps."
+    )]
+    grep_seq: Option<String>,
+
+    #[arg(
+        long,
+        help = "Compute -c/--consensus and -C/--mut from all records instead of only the selected records."
+    )]
+    consensus_all: bool,
+
+    #[arg(
+        long,
+        value_name("KEY"),
+        help = "Sort records. \
+        \"name\": by ID. \
+        \"length\": longest ungapped sequence first. \
+        \"gaps\": fewest gaps first. \
+        \"identity\" or \"identity:ID\": most identical to the first record or the record with ID first, \
+        counting aligned columns without gaps and ignoring case. \
+        \"consensus\": most identical to the consensus of -c/--consensus first, \
+        with its --consensus-* options, -a/--alphabet, and --weights. \
+        \"tree:FILE\": leaf order of a Newick tree, with records missing from the tree last. \
+        Ties keep their input order. \
+        Non-streaming."
+    )]
+    sort: Option<String>,
+
+    #[arg(
+        long,
+        help = "Align the sequences before anything else, e.g. to show unaligned fasta with -c/--consensus or -T/--transpose. \
+        Progressive alignment along a guide tree of k-mer distances, with affine gaps and \
+        BLOSUM62 for proteins or match 5/mismatch -4 for nucleotides. \
+        Sequences are written as fasta on a single line each, with existing gaps removed before aligning. \
+        Fast rather than optimal, meant for up to a few hundred sequences of up to 16 kb. \
+        Non-streaming."
+    )]
+    align: bool,
+
+    // Quality control.
+    #[arg(
+        long,
+        value_name("CHECKS"),
+        num_args(0..=1),
+        require_equals(true),
+        default_missing_value("all"),
+        help = "Highlight problems in sequences and print a summary for each record with problems to stderr. \
+        Optionally a comma separated list of checks, default is \"all\", \
+        which includes \"alphabet\" only if -a/--alphabet is given. \
+        \"alphabet\": chars outside -a/--alphabet. \
+        \"stop\": internal stop codons \"*\". \
+        \"ambiguous\": N and other ambiguity codes in nucleotides, X, B, Z, and J in amino acids. \
+        \"homopolymer\": runs of the same residue of at least --homopolymer length. \
+        \"frameshift\": internal gaps with a length not a multiple of 3 in nucleotide sequences."
+    )]
+    qc: Option<String>,
+
+    #[arg(
+        long("qc-style"),
+        value_name("STYLE"),
+        default_value = "invert",
+        help = "Style for problems found by --qc. See --start."
+    )]
+    qc_style: String,
+
+    #[arg(
+        long,
+        value_name("LENGTH"),
+        default_value_t = 8,
+        help = "Minimum length of homopolymer runs flagged by --qc and --repeats."
+    )]
+    homopolymer: usize,
+
+    #[arg(
+        long,
+        value_name("KINDS"),
+        num_args(0..=1),
+        require_equals(true),
+        default_missing_value("all"),
+        help = "Highlight repetitive sequence on top of the colorscheme. \
+        Optionally a comma separated list of kinds, default is \"all\". \
+        \"homopolymer\": runs of the same residue of at least --homopolymer length. \
+        \"lowcomplexity\": DUST (window 64, score above 2) for nucleotides and SEG-style (window 12, entropy below 2.2 bits) for amino acids. \
+        \"tandem\": short tandem repeats with period 2 to 6 repeated at least 3 times over at least --tandem length. \
+        Gaps are skipped."
+    )]
+    repeats: Option<String>,
+
+    #[arg(
+        long("repeat-style"),
+        value_name("STYLE"),
+        default_value = "underline",
+        help = "Style for repeats found by --repeats. See --start."
+    )]
+    repeat_style: String,
+
+    #[arg(
+        long,
+        value_name("LENGTH"),
+        default_value_t = 12,
+        help = "Minimum total length of tandem repeats highlighted by --repeats."
+    )]
+    tandem: usize,
+
+    // Annotation.
+    #[arg(
+        long,
+        value_name("FILE"),
+        help = "BED file with features to show on sequences with matching IDs. \
+        Coordinates are of the ungapped sequence, and are mapped onto alignment columns. \
+        The name column is used as feature type. \
+        Can be given multiple times."
+    )]
+    bed: Vec<String>,
+
+    #[arg(
+        long,
+        value_name("FILE"),
+        help = "GFF3 file with features to show on sequences with matching IDs. See --bed. \
+        Features are labelled by their Name or ID attribute. \
+        Can be given multiple times."
+    )]
+    gff: Vec<String>,
+
+    #[arg(
+        long,
+        value_name("MODE"),
+        default_value = "row",
+        help = "How to show features from --bed and --gff. \
+        \"row\": extra rows beneath each line of sequence with a labelled bar for each feature. \
+        \"color\": background color of the covered residues by feature type. \
+        Otherwise a style to mark covered residues with, e.g. \"underline\". See --start."
+    )]
+    annotate: String,
+
+    #[arg(
+        long("feature-colors"),
+        value_name("FILE"),
+        help = "File with a feature type and a color on each line separated by tab, comma, or semicolon. \
+        Takes precedence over builtin colors for common types such as gene, exon, CDS, domain, primer, and variant."
+    )]
+    feature_colors: Option<String>,
+
+    #[arg(
+        long,
+        value_name("TYPES"),
+        num_args(0..=1),
+        require_equals(true),
+        default_missing_value("CDS,gene,misc_feature"),
+        help = "Show features from the FEATURES table of GenBank and EMBL records as with --bed and --gff. \
+        Optionally a comma separated list of feature types, default is \"CDS,gene,misc_feature\". \
+        \"all\" for all types except source. \
+        Features are labelled by their label, gene, locus_tag, or product qualifier."
+    )]
+    features: Option<String>,
+
+    #[arg(
+        long("ss"),
+        value_name("[ID=]FILE"),
+        help = "Show secondary structure as rows beneath sequences, with helix and strand colored like \
+        the max of helix_propensity_aa and strand_propensity_aa. \
+        FILE can be a Stockholm file (\"#=GC SS_cons\" and \"#=GR <ID> SS\" lines, per alignment column), \
+        a PSIPRED .ss2 file, a fasta file of DSSP strings with record IDs, or a file of a plain DSSP string (per residue). \
+        Prefix with \"ID=\" to attach to the record with that ID, otherwise structure without an ID is shown for all records. \
+        Can be given multiple times."
+    )]
+    structure: Vec<String>,
+
+    #[arg(
+        long("ss-tint"),
+        help = "Tint residues by their secondary structure from --ss."
+    )]
+    ss_tint: bool,
+
+    #[arg(
+        long,
+        value_name("FILE"),
+        help = "VCF file with variants to highlight on reference sequences with IDs matching CHROM. \
+        Positions are of the ungapped reference. \
+        Styles are given by --snv, --ins, and --del."
+    )]
+    vcf: Option<String>,
+
+    #[arg(
+        long,
+        help = "Show a row for each sample in --vcf with its genotype beneath each line of sequence, in the order of the VCF. \
+        \".\" is homozygous reference, SNVs show the alt base, insertions \"+\", and deletions \"-\". \
+        Heterozygous alleles are dimmed. \
+        Rows start with the sample name and sequence lines with the record ID, padded to the same width."
+    )]
+    genotypes: bool,
+
+    #[arg(
+        long("snv"),
+        value_name("STYLE"),
+        default_value = "invert",
+        help = "Style for SNVs from --vcf. See --start."
+    )]
+    snv_style: String,
+
+    #[arg(
+        long("ins"),
+        value_name("STYLE"),
+        default_value = "underline",
+        help = "Style for the base before insertions from --vcf. See --start."
+    )]
+    ins_style: String,
+
+    #[arg(
+        long("del"),
+        value_name("STYLE"),
+        default_value = "strike",
+        help = "Style for deleted bases from --vcf. See --start."
+    )]
+    del_style: String,
+
+    // Codons.
+    #[arg(
+        long,
+        help = "Color each base by the amino acid its codon encodes, using the -s/--bg and -S/--fg colorschemes, \
+        e.g. \"-s clustal_aa\". \
+        Gaps are skipped when reading codons, and bases not part of a full codon are left uncolored. \
+        A sequence is a fasta or fastq record, which may be wrapped over multiple lines."
+    )]
+    codon: bool,
+
+    #[arg(
+        long,
+        value_name("FRAME"),
+        default_value = "1",
+        allow_hyphen_values = true,
+        help = "Reading frame for --codon and --translate. \
+        1, 2, or 3 for the forward strand, or -1, -2, or -3 for the reverse complement strand."
+    )]
+    frame: String,
+
+    #[arg(
+        long("table"),
+        value_name("ID"),
+        default_value_t = 1,
+        help = "NCBI genetic code table ID for --codon and --translate, \
+        e.g. 2 for vertebrate mitochondrial and 11 for bacterial. \
+        Use --list-tables to list available tables."
+    )]
+    genetic_code: u8,
+
+    #[arg(
+        long,
+        value_name("FRAMES"),
+        num_args(0..=1),
+        require_equals(true),
+        default_missing_value("frame"),
+        allow_hyphen_values = true,
+        help = "Show the translated protein beneath each line of sequence, \
+        with each amino acid beneath the middle base of its codon. \
+        Optionally a comma separated list of frames, or \"all\" for six-frame translation. \
+        Default is the frame given by --frame."
+    )]
+    translate: Option<String>,
+
+    #[arg(
+        long("start"),
+        value_name("STYLE"),
+        default_value = "underline",
+        help = "Indicate start codons with \"bold\", \"dim\", \"italic\", \"underline\", \"invert\", \"strike\", or a background color. \
+        Empty string to disable. Applies to --codon and --translate."
+    )]
+    start_style: String,
+
+    #[arg(
+        long("stop"),
+        value_name("STYLE"),
+        default_value = "invert",
+        help = "Indicate stop codons. See --start."
+    )]
+    stop_style: String,
+
+    // Structure files.
+    #[arg(
+        long("pdb-seq"),
+        value_name("SOURCE"),
+        default_value = "seqres",
+        help = "Sequence of each chain in PDB and mmCIF input. \
+        \"seqres\": the full sequence from SEQRES or _pdbx_poly_seq_scheme, or observed residues if not given. \
+        \"observed\": residues with coordinates in the first model."
+    )]
+    pdb_seq: String,
+
+    #[arg(
+        long,
+        value_name("SCALE"),
+        num_args(0..=1),
+        require_equals(true),
+        default_missing_value("range"),
+        help = "Color the background of residues in PDB and mmCIF input by their mean B-factor, \
+        e.g. pLDDT of AlphaFold models. Uses observed residues. \
+        \"range\" (default): blue through white to red from lowest to highest value of each chain. \
+        \"plddt\": AlphaFold confidence colors from orange (below 50) through yellow and light blue to dark blue (above 90)."
+    )]
+    bfactor: Option<String>,
+
+    // Tables.
+    #[arg(
+        long("table-cols"),
+        value_name("COLUMNS"),
+        value_delimiter(','),
+        help = "Treat input as a table with a header line and only color the cells of the given columns, \
+        selected by header name or 1-based index, e.g. \"sequence,cdr3\". \
+        Fields may be quoted with double quotes. \
+        -r/--regex and -m/--min apply within each cell, \
+        and -c/--consensus and -C/--mut are computed over the selected columns across rows, which aligns columns."
+    )]
+    table_cols: Option<Vec<String>>,
+
+    #[arg(
+        long,
+        value_name("CHAR"),
+        help = "Delimiter for --table-cols, e.g. \",\" or \"tab\". \
+        Default is guessed from the header as the one of tab, comma, semicolon, or pipe giving most fields."
+    )]
+    delimiter: Option<String>,
+
+    #[arg(
+        long("table-align"),
+        help = "Pad cells for --table-cols such that columns line up across rows, \
+        e.g. so equal length sequences line up. \
+        Unquoted cells of selected columns with quoted cells are indented by a space. \
+        Non-streaming."
+    )]
+    table_align: bool,
+
+    // JSON.
+    #[arg(
+        long("json-fields"),
+        value_name("PATHS"),
+        value_delimiter(','),
+        help = "Treat input as JSON or JSON Lines and only color string values of fields with the given key paths, \
+        e.g. \"heavy,light\" or \"chains.*.seq\" where \"*\" matches any key. \
+        Arrays are transparent, so \"chains.seq\" matches the seq field of each object in a chains array. \
+        The document is otherwise passed through unchanged."
+    )]
+    json_fields: Option<Vec<String>>,
+
+    #[arg(
+        long("json-pretty"),
+        help = "Pretty-print documents for --json-fields with an indentation of 2 spaces."
+    )]
+    json_pretty: bool,
+
+    // Pileup.
+    #[arg(
+        long,
+        help = "Read SAM alignments and show reads laid out against reference coordinates using their POS and CIGAR, \
+        similar to samtools tview. \
+        Insertions add columns where other reads show \"*\", and deletions are shown as \"*\". \
+        Rows are a header, a ruler, the reference if --ref is given, and reads packed into rows. \
+        Non-streaming."
+    )]
+    pileup: bool,
+
+    #[arg(
+        long("ref"),
+        value_name("FILE"),
+        help = "Reference fasta for --pileup. Bases matching the reference are shown according to --matches."
+    )]
+    reference: Option<String>,
+
+    #[arg(
+        long,
+        value_name("REGION"),
+        help = "Only show the region NAME, NAME:START, or NAME:START-END with 1-based inclusive coordinates. \
+        For --pileup default is the extent of the reads on each reference. \
+        Otherwise regions are read directly from fasta files using a samtools .fai index, \
+        which is created if missing, and headers are annotated with the region. \
+        Fasta compressed with bgzip is read using its .gzi index, also created if missing. \
+        Can be given multiple times."
+    )]
+    region: Vec<String>,
+
+    #[arg(
+        long,
+        value_name("STYLE"),
+        default_value = "dot",
+        help = "How to show bases matching --ref in --pileup. \
+        \"dot\": \".\" for forward and \",\" for reverse strand reads as samtools tview. \
+        Otherwise a style to apply to the colored base, e.g. \"dim\". See --start."
+    )]
+    matches: String,
+
+    #[arg(
+        long,
+        help = "Scale brightness of bases in --pileup by their base quality, with full brightness from phred 40."
+    )]
+    quality: bool,
+
+    #[arg(
+        long,
+        value_name("MODE"),
+        default_value = "strip",
+        help = "How to handle ANSI escape sequences in the input, e.g. from grep --color or a previous seqcol run. \
+        \"strip\": remove them, so coloring already colored text gives the same result. \
+        \"keep\": pass them through and leave text styled by them uncolored, e.g. to keep grep highlights. \
+        Not with -c/--consensus or -C/--mut."
+    )]
+    ansi: String,
+
+    #[arg(
+        long,
+        value_name("N"),
+        default_value_t = 1,
+        help = "Number of threads. 0 uses all available cores. \
+        Input is colored in chunks in parallel, keeping the order of lines, \
+        up to N input files are read ahead concurrently, and consensus columns are counted in parallel. \
+        Chunks are written when done, so output may appear in bursts for slow input."
+    )]
+    threads: usize,
+
     // Misc options.
     #[arg(
         short('l'),
@@ -234,6 +860,12 @@ struct Args {
     )]
     list_colorschemes: bool,
 
+    #[arg(
+        long("list-tables"),
+        help = "List available genetic code tables."
+    )]
+    list_tables: bool,
+
     #[arg(
         short('p'),
         long("paging"),
@@ -248,21 +880,99 @@ struct Args {
         If $PAGER is set to less with custom args, -R is added automatically for ANSI color support."
     )]
     paging: String,
+
+    #[command(subcommand)]
+    command: Option<Subcommand>,
+}
+
+#[derive(Debug, clap::Subcommand)]
+enum Subcommand {
+    #[command(
+        about = "Align two sequences and show them with a match line and identity statistics.",
+        long_about = "Align two sequences and show them with a match line and identity statistics. \
+        Colouring options are given before the subcommand, e.g. `seqcol -s jalview_nucl diff A B`. \
+        The alignment takes a byte per pair of positions, up to 256 MB, e.g. two sequences of 16 kb."
+    )]
+    Diff(DiffArgs),
+}
+
+#[derive(Debug, clap::Args)]
+struct DiffArgs {
+    #[arg(
+        value_name = "A",
+        help = "First sequence as a file (its first record), FILE:ID for a record by ID, or the sequence itself. \
+        A file alone gives its first two records."
+    )]
+    a: String,
+
+    #[arg(value_name = "B", help = "Second sequence, as for A.")]
+    b: Option<String>,
+
+    #[arg(
+        long,
+        default_value = "global",
+        help = "\"global\" (Needleman-Wunsch) aligns the full sequences, \
+        \"local\" (Smith-Waterman) only the best matching part of them."
+    )]
+    mode: String,
+
+    #[arg(
+        long,
+        value_name("SCORING"),
+        default_value = "auto",
+        help = "\"nucl\" scores matches 5 and mismatches -4 with gap open 10 and extend 1. \
+        \"protein\" scores with BLOSUM62 with gap open 11 and extend 1. \
+        \"auto\" (default) uses nucl if both sequences look like nucleotides."
+    )]
+    scoring: String,
+
+    #[arg(long("match"), value_name("SCORE"), help = "Score of identical residues. With --mismatch replaces BLOSUM62.")]
+    match_score: Option<i32>,
+
+    #[arg(long, value_name("SCORE"), allow_negative_numbers = true, help = "Score of different residues.")]
+    mismatch: Option<i32>,
+
+    #[arg(long, value_name("PENALTY"), help = "Penalty for opening a gap, which is added to the extension penalty of its first position.")]
+    gap_open: Option<i32>,
+
+    #[arg(long, value_name("PENALTY"), help = "Penalty for each position of a gap.")]
+    gap_extend: Option<i32>,
+
+    #[arg(long, default_value_t = 60, help = "Alignment columns per block.")]
+    width: usize,
+
+    #[arg(
+        long,
+        value_name("STYLE"),
+        default_value = "invert",
+        help = "Highlight of residues differing from the other sequence. \
+        An attribute (bold, dim, italic, underline, invert, strike) or a background color. \
+        Empty string disables highlighting."
+    )]
+    mismatch_style: String,
 }
 
 fn main() {
     if let Err(e) = run(Args::parse()) {
         // Silently exit on broken pipe (e.g., when pager closes early).
-        if let Some(io_err) = e.downcast_ref::<io::Error>() {
-            if io_err.kind() == io::ErrorKind::BrokenPipe {
-                std::process::exit(0);
-            }
+        if let Some(io_err) = e.downcast_ref::<io::Error>()
+            && io_err.kind() == io::ErrorKind::BrokenPipe
+        {
+            std::process::exit(0);
         }
         eprintln!("{e}");
         std::process::exit(1);
     }
 }
 
+// Parse a highlight style where empty string means no highlighting.
+fn parse_highlight(s: &str) -> Option<Highlight> {
+    match s {
+        "" => None,
+        s => Some(Highlight::parse(s).expect(s)),
+    }
+}
+
 fn run(args: Args) -> Result<()> {
     if args.list_colorschemes {
         let names = colorschemes::get_colorscheme_names();
@@ -270,6 +980,15 @@ fn run(args: Args) -> Result<()> {
         exit(0)
     }
 
+    if args.list_tables {
+        let mut ids: Vec<&u8> = GENETIC_CODES.keys().collect();
+        ids.sort();
+        for id in ids {
+            println!("{id}\t{}", GENETIC_CODES[id].name);
+        }
+        exit(0)
+    }
+
     let schemes = colorschemes::load_colorschemes();
 
     // Read colorschemes
@@ -280,7 +999,7 @@ fn run(args: Args) -> Result<()> {
             let mut colors: HashMap<char, Color> = HashMap::new();
             for scheme_name in scheme_names {
                 // Ignore empty string, which allows for disabling bg coloring all together.
-                if scheme_name != "" {
+                if !scheme_name.is_empty() {
                     match schemes.get(&scheme_name) {
                         Some(_colors) => colors.extend(_colors),
                         None => colors.extend(
@@ -314,7 +1033,7 @@ fn run(args: Args) -> Result<()> {
             let mut colors: HashMap<char, Color> = HashMap::new();
             for scheme_name in scheme_names {
                 // Ignore empty string, which allows for disabling bg coloring all together.
-                if scheme_name != "" {
+                if !scheme_name.is_empty() {
                     match schemes.get(&scheme_name) {
                         Some(_colors) => colors.extend(_colors),
                         None => colors.extend(
@@ -335,8 +1054,7 @@ fn run(args: Args) -> Result<()> {
                 for (&c, col) in colors_bg.iter() {
                     colors_fg.insert(c, col.to_owned());
                 }
-            } else if invisible.starts_with("^") {
-                let visible = &invisible[1..];
+            } else if let Some(visible) = invisible.strip_prefix('^') {
                 for (&c, col) in colors_bg.iter() {
                     if !visible.contains(c) {
                         colors_fg.insert(c, col.to_owned());
@@ -384,10 +1102,31 @@ fn run(args: Args) -> Result<()> {
         }
     }
 
+    let case = Case::parse(&args.case).map_err(|_| {
+        anyhow::anyhow!(
+            "Case mode not understood: '{}'. Use \"sensitive\", \"insensitive\", \"hide\", a style, or a color.",
+            args.case
+        )
+    })?;
+    case.apply(&mut styles);
+    let hide_lowercase = case.hide();
+    // GenBank and EMBL sequences are lowercase by convention.
+    let mut flat_file_styles = styles.clone();
+    Case::Insensitive.apply(&mut flat_file_styles);
+
     let comp_consensus = args.consensus.is_some() || args.mutations.is_some();
+    let consensus_case = ConsensusCase::parse(&args.consensus_case)?;
 
     // Read alphabet arg if relevant.
-    let alphabet: Option<HashSet<char>> = if comp_consensus || args.min_seq_length.is_some() {
+    let qc_checks = match &args.qc {
+        None => vec![],
+        Some(checks) => Check::parse_list(checks, args.alphabet.is_some()).map_err(anyhow::Error::msg)?,
+    };
+    if qc_checks.contains(&Check::Alphabet) && args.alphabet.is_none() {
+        anyhow::bail!("QC check \"alphabet\" requires -a/--alphabet.");
+    }
+
+    let alphabet: Option<HashSet<char>> = if comp_consensus || args.min_seq_length.is_some() || !qc_checks.is_empty() {
         match args.alphabet {
             None => None,
             Some(arg) => {
@@ -416,6 +1155,18 @@ fn run(args: Args) -> Result<()> {
     } else {
         None
     };
+    let consensus_opts = ConsensusOpts {
+        alphabet: alphabet.clone(),
+        case: consensus_case,
+        threshold: args.consensus_threshold,
+        exclude_gaps: args.exclude_gaps,
+        ties: Ties::parse(&args.consensus_ties)?,
+        henikoff: match args.weights.as_str() {
+            "none" => false,
+            "henikoff" => true,
+            s => anyhow::bail!("Sequence weights not understood: '{s}'. Use \"none\" or \"henikoff\"."),
+        },
+    };
 
     let mut regexes = vec![];
 
@@ -468,247 +1219,674 @@ fn run(args: Args) -> Result<()> {
         _ => &mut stdout_lock,
     };
 
+    if let Some(Subcommand::Diff(diff_args)) = &args.command {
+        let (a, b) = read_sequences(&diff_args.a, diff_args.b.as_deref())?;
+        let protein = match diff_args.scoring.as_str() {
+            "auto" => !(bio::is_nucleotide(&a.residues) && bio::is_nucleotide(&b.residues)),
+            "nucl" => false,
+            "protein" => true,
+            s => anyhow::bail!("Scoring not understood: '{s}'. Use \"auto\", \"nucl\", or \"protein\"."),
+        };
+        let mut scoring = Scoring::new(protein);
+        if diff_args.match_score.is_some() || diff_args.mismatch.is_some() {
+            scoring.blosum = false;
+        }
+        scoring.match_score = diff_args.match_score.unwrap_or(scoring.match_score);
+        scoring.mismatch = diff_args.mismatch.unwrap_or(scoring.mismatch);
+        scoring.gap_open = diff_args.gap_open.unwrap_or(scoring.gap_open);
+        scoring.gap_extend = diff_args.gap_extend.unwrap_or(scoring.gap_extend);
+        let opts = DiffOpts {
+            mode: Mode::parse(&diff_args.mode)?,
+            scoring,
+            width: diff_args.width,
+            mismatch: parse_highlight(&diff_args.mismatch_style),
+        };
+        write_diff(output, &a, &b, &styles, hide_lowercase, &opts)?;
+        output.flush()?;
+        return close_pager(pager_stdin, pager_child);
+    }
+
     let newline = ansi_byte('\n');
-    let space = ansi_byte(' ');
 
-    if !args.transpose && !comp_consensus {
-        // Streaming.
-        let lines = read_lines(args.files)?;
+    // Record level painting, i.e. of sequences that may be wrapped over multiple lines.
+    let codon_opts = if args.codon || args.translate.is_some() {
+        let frame = Frame::parse(&args.frame).map_err(anyhow::Error::msg)?;
+        let translate = match args.translate.as_deref() {
+            None => vec![],
+            Some("frame") => vec![frame],
+            Some(frames) => Frame::parse_list(frames).map_err(anyhow::Error::msg)?,
+        };
+        let code = GeneticCode::get(args.genetic_code).ok_or_else(|| {
+            anyhow::anyhow!("Unknown genetic code table: {}. See --list-tables.", args.genetic_code)
+        })?;
+        Some(CodonOpts {
+            code,
+            frame,
+            color: args.codon,
+            translate,
+            start: parse_highlight(&args.start_style),
+            stop: parse_highlight(&args.stop_style),
+        })
+    } else {
+        None
+    };
+    let transforms: Vec<Transform> = args
+        .transforms
+        .iter()
+        .map(|s| Transform::parse(s))
+        .collect::<Result<_, _>>()
+        .map_err(anyhow::Error::msg)?;
+    let qc_opts = if qc_checks.is_empty() {
+        None
+    } else {
+        Some(QcOpts {
+            checks: qc_checks,
+            alphabet: alphabet.clone(),
+            homopolymer: args.homopolymer,
+            style: Highlight::parse(&args.qc_style).expect(&args.qc_style),
+        })
+    };
+    let repeat_opts = match &args.repeats {
+        None => None,
+        Some(kinds) => Some(RepeatOpts {
+            kinds: Repeat::parse_list(kinds).map_err(anyhow::Error::msg)?,
+            homopolymer: args.homopolymer,
+            tandem: args.tandem,
+            style: Highlight::parse(&args.repeat_style).expect(&args.repeat_style),
+        }),
+    };
+    let annotation_opts = if args.bed.is_empty() && args.gff.is_empty() && args.features.is_none() {
+        None
+    } else {
+        let mut features = Features::new();
+        for path in &args.bed {
+            read_bed(path, &mut features)?;
+        }
+        for path in &args.gff {
+            read_gff(path, &mut features)?;
+        }
+        Some(AnnotationOpts {
+            features,
+            flat_file: args.features.as_ref().map(|kinds| match kinds.as_str() {
+                "all" => vec![],
+                kinds => kinds.split(',').map(|k| k.trim().to_lowercase()).collect(),
+            }),
+            colors: match &args.feature_colors {
+                None => HashMap::new(),
+                Some(path) => read_feature_colors(path)?,
+            },
+            mode: AnnotationMode::parse(&args.annotate).map_err(anyhow::Error::msg)?,
+        })
+    };
+    let structure_opts = if args.structure.is_empty() {
+        None
+    } else {
+        let mut structures = Structures::new();
+        for arg in &args.structure {
+            read_structure(arg, &mut structures)?;
+        }
+        Some(StructureOpts { structures, tint: args.ss_tint })
+    };
+    let variant_opts = match &args.vcf {
+        None => None,
+        Some(path) => Some(VariantOpts {
+            vcf: read_vcf(path)?,
+            snv: Highlight::parse(&args.snv_style).expect(&args.snv_style),
+            insertion: Highlight::parse(&args.ins_style).expect(&args.ins_style),
+            deletion: Highlight::parse(&args.del_style).expect(&args.del_style),
+            genotypes: args.genotypes,
+        }),
+    };
 
-        match regexes.len() {
-            0 => {
-                // No filters, simply color every line.
-                for line in lines {
-                    write_ansi(output, &styles, &line)?;
-                    output.write(&newline)?;
+    let regions: Vec<Region> = args
+        .region
+        .iter()
+        .map(|s| Region::parse(s))
+        .collect::<Result<_, _>>()
+        .map_err(anyhow::Error::msg)?;
+
+    let keep_ansi = match args.ansi.as_str() {
+        "strip" => false,
+        "keep" => true,
+        s => anyhow::bail!("ANSI mode not understood: '{s}'. Use \"strip\" or \"keep\"."),
+    };
+    // Kept escape sequences would take up columns and shift them between lines.
+    if keep_ansi && comp_consensus {
+        anyhow::bail!("--ansi keep can't be combined with -c/--consensus or -C/--mut, which count columns.");
+    }
+    let threads = match args.threads {
+        0 => std::thread::available_parallelism().map_or(1, |n| n.get()),
+        n => n,
+    };
+    // Whether the input can be read again from the files, i.e. regular files rather than stdin,
+    // FIFOs, or process substitution.
+    let mut seekable = args.files.iter().all(|f| f != "-" && std::fs::metadata(f).is_ok_and(|m| m.is_file()));
+    let lines = if !regions.is_empty() && !args.pileup {
+        // Seek to regions of indexed fasta files.
+        if !seekable {
+            anyhow::bail!("--region needs fasta files to index, which can't be read from stdin or a pipe.");
+        }
+        seekable = false;
+        let indexes: Vec<Faidx> = args.files.iter().map(|path| Faidx::open(path)).collect::<Result<_>>()?;
+        let mut fasta = vec![];
+        for region in &regions {
+            match indexes.iter().find(|index| index.contains(&region.name)) {
+                Some(index) => fasta.extend(index.fetch(region)?),
+                None => anyhow::bail!("Sequence '{}' not found in {}.", region.name, args.files.join(", ")),
+            }
+        }
+        Lines::from_lines(fasta)
+    } else {
+        read_lines(args.files.clone(), threads)?
+    };
+    let mut lines = lines.strip_ansi(!keep_ansi);
+    let first = lines.peek();
+    // GenBank and EMBL records are painted by record to only color their sequence blocks.
+    let flat_file = is_flat_file_header(&first);
+    let mut bfactor_opts = None;
+    // Structure and alignment formats given by --from or by the extension shared by all files.
+    fn shared<T: Copy + PartialEq>(formats: Vec<Option<T>>) -> Option<T> {
+        formats.iter().all(|&f| f == formats[0]).then_some(formats[0]).flatten()
+    }
+    let (pdb_format, phylo_format) = match args.from.as_deref().map(str::to_lowercase).as_deref() {
+        Some("pdb") => (Some(pdb::Format::Pdb), None),
+        Some("mmcif" | "cif") => (Some(pdb::Format::Mmcif), None),
+        Some(s) => match phylo::Format::parse(s) {
+            Ok(format) => (None, Some(format)),
+            Err(_) => anyhow::bail!(
+                "Format not understood: '{s}'. Use \"fasta\", \"phylip\", \"phylip-strict\", \"nexus\", \"pdb\", or \"mmcif\"."
+            ),
+        },
+        None => (
+            shared(args.files.iter().map(|f| pdb::Format::from_path(f)).collect()),
+            shared(args.files.iter().map(|f| phylo::Format::from_path(f)).collect()),
+        ),
+    };
+    let mut lines = match pdb_format {
+        None => match phylo_format {
+            None | Some(phylo::Format::Fasta) => lines,
+            Some(format) => {
+                seekable = false;
+                let seqs = match format {
+                    phylo::Format::Nexus => phylo::read_nexus(lines)?,
+                    _ => phylo::read_phylip(lines)?,
+                };
+                Lines::from_lines(seqs.into_iter().flat_map(|(name, seq)| [format!(">{name}"), seq]))
+            }
+        },
+        Some(format) => {
+            seekable = false;
+            let source = match args.bfactor {
+                Some(_) => Source::Observed,
+                None => Source::parse(&args.pdb_seq)?,
+            };
+            let mut fasta = vec![];
+            let mut values = HashMap::new();
+            // Each file is a structure of its own, with its own chains and models.
+            for chain in lines.files().into_iter().flat_map(|lines| read_chains(lines, format, source)) {
+                fasta.push(format!(">{}", chain.name));
+                fasta.push(chain.seq.into_iter().collect());
+                values.insert(chain.name, chain.values);
+            }
+            if let Some(scale) = &args.bfactor {
+                bfactor_opts = Some(BfactorOpts { values, scale: Scale::parse(scale)? });
+            }
+            Lines::from_lines(fasta)
+        }
+    };
+    if args.align {
+        // Not streaming.
+        if args.table_cols.is_some() {
+            anyhow::bail!("--align aligns sequence records, not --table-cols rows.");
+        }
+        seekable = false;
+        lines = Lines::from_lines(msa::align_records(records(lines).collect())?);
+    }
+    if let Some(key) = &args.sort {
+        // Not streaming.
+        if args.table_cols.is_some() {
+            anyhow::bail!("--sort sorts sequence records, not --table-cols rows.");
+        }
+        seekable = false;
+        let mut sorted: Vec<Record<String>> = records(lines).collect();
+        sort(&mut sorted, &SortKey::parse(key)?, &consensus_opts)?;
+        lines = Lines::from_lines(sorted.into_iter().flat_map(|record| record.into_lines()));
+    }
+
+    let columns = args.columns.as_deref().map(parse_columns).transpose().map_err(anyhow::Error::msg)?;
+    let output_format = args.to.as_deref().map(phylo::Format::parse).transpose()?;
+    let prepare_record = |record: &mut Record<String>| {
+        if let Some(columns) = columns {
+            slice(record, columns);
+        }
+        if !transforms.is_empty() {
+            transform(record, &transforms, args.suffix.as_deref());
+        }
+    };
+
+    let table = match &args.table_cols {
+        None => None,
+        Some(specs) => {
+            let delimiter = match args.delimiter.as_deref() {
+                None => None,
+                Some("tab" | "\\t") => Some('\t'),
+                Some(d) => d.chars().next(),
+            };
+            Some(Table::new(lines.next().unwrap_or_default(), delimiter, specs)?)
+        }
+    };
+    let paint_cell = |text: &str| paint(&styles, &regexes, hide_lowercase, text);
+
+    let selection = Selection::new(
+        &args.select,
+        args.select_file.as_deref(),
+        args.grep_header.as_deref(),
+        args.grep_seq.as_deref(),
+    )?;
+    let selected = |record: &Record<String>| selection.as_ref().is_none_or(|s| s.matches(record));
+    // Whether unselected records are still painted to be counted for consensus.
+    let count_all = args.consensus_all && selection.is_some();
+    let mut groups = Groups::new(args.group_regex.as_deref(), args.group_file.as_deref())?;
+    if groups.is_grouped() && !comp_consensus {
+        anyhow::bail!("--group-regex and --group-file need -c/--consensus or -C/--mut.");
+    }
+    if groups.is_grouped() && table.is_some() {
+        anyhow::bail!("--group-regex and --group-file group sequence records, not --table-cols rows.");
+    }
+
+    let by_record = codon_opts.is_some()
+        || variant_opts.is_some()
+        || structure_opts.is_some()
+        || !transforms.is_empty()
+        || qc_opts.is_some()
+        || repeat_opts.is_some()
+        || annotation_opts.is_some()
+        || bfactor_opts.is_some()
+        || columns.is_some()
+        || selection.is_some();
+
+    let n_records = Cell::new(0);
+    // Whether to skip reporting to stderr, e.g. in the first of two passes over the input.
+    let quiet = Cell::new(false);
+    let paint_record = |mut record: Record<String>| -> Record<Line> {
+        n_records.set(n_records.get() + 1);
+        prepare_record(&mut record);
+        let name = match record.id() {
+            Some(id) => id.to_string(),
+            None => format!("record {}", n_records.get()),
+        };
+        let own_features = match annotation_opts.as_ref().and_then(|opts| opts.flat_file.as_ref()) {
+            Some(kinds) if record.blocks => read_flat_file_features(&record.meta, kinds),
+            _ => vec![],
+        };
+        let mut record = if record.blocks {
+            // Only the letters of sequence blocks are sequence in GenBank and EMBL records.
+            let seq = std::mem::take(&mut record.seq);
+            let mut painted = record.map(|line| Line::new(line.chars().map(Char::Unstyled).collect()));
+            painted.seq = seq
+                .iter()
+                .map(|line| {
+                    let chars = paint(&flat_file_styles, &regexes, hide_lowercase, line).into_iter().map(|ch| match ch {
+                        Char::Styled(p) if !p.value.is_alphabetic() => Char::Unstyled(p.value),
+                        ch => ch,
+                    });
+                    Line::new(chars.collect())
+                })
+                .collect();
+            painted
+        } else {
+            record.map(|line| Line::new(paint(&styles, &regexes, hide_lowercase, &line)))
+        };
+        if let Some(opts) = &bfactor_opts {
+            paint_bfactors(&mut record.seq, &name, opts);
+        }
+        if let Some(opts) = &codon_opts {
+            paint_codons(&mut record.seq, &styles, opts);
+        }
+        if let Some(opts) = &repeat_opts {
+            paint_repeats(&mut record.seq, opts);
+        }
+        if let Some(opts) = &qc_opts {
+            let summary = qc(&mut record.seq, opts);
+            if !summary.is_empty() && !quiet.get() {
+                eprintln!("{name}: {}", format_summary(&summary));
+            }
+        }
+        if let Some(opts) = &structure_opts {
+            paint_structure(&mut record.seq, &name, opts);
+        }
+        if let Some(opts) = &variant_opts {
+            paint_variants(&mut record.seq, &name, &styles, opts);
+        }
+        if let Some(opts) = &annotation_opts {
+            let features: Vec<&Feature> = opts.features.get(&name).into_iter().flatten().chain(&own_features).collect();
+            annotate(&mut record.seq, &features, opts);
+        }
+        record
+    };
+
+    let consensus_hl = args.consensus.as_ref().or(args.mutations.as_ref()).map(|s| Highlight::parse(s).expect(s));
+
+    if args.pileup {
+        // Not streaming.
+        let mut reference = HashMap::new();
+        if let Some(path) = &args.reference {
+            for record in records(read_lines(vec![path.clone()], 1)?) {
+                if let Some(id) = record.id() {
+                    let seq = record.seq.iter().flat_map(|l| l.chars()).collect();
+                    reference.insert(id.to_string(), seq);
                 }
             }
-            1 => {
-                let re = &regexes[0];
-                for line in lines {
+        }
+        let opts = PileupOpts {
+            matches: match args.matches.as_str() {
+                "dot" => None,
+                s => Some(Highlight::parse(s).expect(s)),
+            },
+            quality: args.quality,
+        };
+        let rows = pileup(lines, &reference, &regions, &styles, &opts)?;
+        if !args.transpose {
+            for row in &rows {
+                row.write(output)?;
+            }
+        } else {
+            write_transposed(output, &rows)?;
+        }
+    } else if let Some(format) = output_format {
+        // Not streaming.
+        let mut seqs = vec![];
+        for (n, mut record) in records(lines).filter(selected).enumerate() {
+            prepare_record(&mut record);
+            let name = record.id().map_or_else(|| format!("seq{}", n + 1), |id| id.to_string());
+            let seq = record
+                .seq
+                .iter()
+                .flat_map(|line| line.chars())
+                .filter(|c| !c.is_whitespace() && (!record.blocks || c.is_alphabetic()))
+                .collect();
+            seqs.push((name, seq));
+        }
+        phylo::write_alignment(output, format, &seqs)?;
+    } else if let Some(paths) = &args.json_fields {
+        // Streaming.
+        let mut json = Json::new(paths, args.json_pretty);
+        for line in lines {
+            let chars = json.line(&line, paint_cell);
+            if args.json_pretty {
+                for ch in &chars {
+                    ch.write(output)?;
+                }
+            } else {
+                Line::new(chars).write(output)?;
+            }
+        }
+    } else if let Some(table) = table.as_ref().filter(|_| !args.transpose && !comp_consensus && !args.table_align) {
+        // Streaming.
+        table.join(table.header_fields(), &[]).write(output)?;
+        for line in lines {
+            table.join(table.fields(&line, paint_cell), &[]).write(output)?;
+        }
+    } else if table.is_none() && !args.transpose && !comp_consensus && !by_record && !flat_file && !keep_ansi {
+        // Streaming line by line with reused buffers and escape sequences looked up per byte.
+        let table = StyleTable::new(&styles, hide_lowercase);
+        let color = |out: &mut Vec<u8>, text: &[u8]| -> io::Result<()> {
+            match &table {
+                Some(table) => table.write(out, text),
+                None => {
+                    write_ansi(out, &styles, hide_lowercase, &String::from_utf8_lossy(text))?;
+                }
+            }
+            Ok(())
+        };
+        let regexes: Vec<regex::bytes::Regex> = regexes
+            .iter()
+            .map(|re| regex::bytes::Regex::new(re.as_str()).unwrap())
+            .collect();
+        let color_line = |out: &mut Vec<u8>, line: &[u8]| -> io::Result<()> {
+            match regexes.as_slice() {
+                // No filters, simply color every line.
+                [] => color(out, line)?,
+                [re] => {
                     let mut i = 0;
-                    for m in re.find_iter(&line) {
-                        output.write(&line[i..m.start()].as_bytes())?;
-                        write_ansi(output, &styles, m.as_str())?;
+                    for m in re.find_iter(line) {
+                        out.extend_from_slice(&line[i..m.start()]);
+                        color(out, m.as_bytes())?;
                         i = m.end();
                     }
-                    output.write(&line[i..].as_bytes())?;
-                    output.write(&newline)?;
+                    out.extend_from_slice(&line[i..]);
                 }
-            }
-            2 => {
-                // Boolean logic: color only if both regex filters says yes.
-                let re0 = &regexes[0];
-                let re1 = &regexes[1];
-                for line in lines {
+                [re0, re1] => {
+                    // Boolean logic: color only if both regex filters says yes.
                     let mut i = 0;
-                    for m0 in re0.find_iter(&line) {
-                        output.write(&line[i..m0.start()].as_bytes())?;
+                    for m0 in re0.find_iter(line) {
+                        out.extend_from_slice(&line[i..m0.start()]);
                         i = m0.start();
-                        for m1 in re1.find_iter(m0.as_str()) {
-                            output.write(&line[i..m1.start()].as_bytes())?;
-                            write_ansi(output, &styles, m1.as_str())?;
-                            i = m1.end();
+                        for m1 in re1.find_iter(m0.as_bytes()) {
+                            out.extend_from_slice(&line[i..m0.start() + m1.start()]);
+                            color(out, m1.as_bytes())?;
+                            i = m0.start() + m1.end();
                         }
-                        output.write(&line[i..m0.end()].as_bytes())?;
+                        out.extend_from_slice(&line[i..m0.end()]);
                         i = m0.end();
                     }
-                    output.write(&line[i..].as_bytes())?;
-                    output.write(&newline)?;
+                    out.extend_from_slice(&line[i..]);
                 }
+                _ => unimplemented!(), // Unreachable
             }
-            _ => unimplemented!(), // Unreachable
+            out.push(b'\n');
+            Ok(())
         };
-    } else {
-        // Not streaming.
-        // First read input into memory.
-        let (lines, max_line) = inout::read_lines_max(args.files)?;
-
-        // Gather styles according to each char in each line.
-        let mut lines_painted: Vec<Vec<Char>> = Vec::with_capacity(lines.len());
-
-        match regexes.len() {
-            0 => {
-                for line in lines {
-                    lines_painted.push(to_painted(&styles, &line).collect());
+        let mut output = BufWriter::with_capacity(1 << 16, &mut *output);
+        let mut line = vec![];
+        if threads == 1 {
+            let mut out = vec![];
+            while lines.read_into(&mut line)? {
+                out.clear();
+                color_line(&mut out, &line)?;
+                output.write_all(&out)?;
+                // Don't hold back output while waiting for more input.
+                if lines.buffered() == 0 {
+                    output.flush()?;
                 }
             }
-            1 => {
-                let re = &regexes[0];
-                for line in lines {
-                    let mut line_painted: Vec<Char> = Vec::with_capacity(line.len());
-                    let mut i = 0;
-                    for m in re.find_iter(&line) {
-                        for c in line[i..m.start()].chars() {
-                            line_painted.push(Char::Unstyled(c));
-                        }
-                        line_painted.extend(to_painted(&styles, m.as_str()));
-                        i = m.end();
-                    }
-                    for c in line[i..].chars() {
-                        line_painted.push(Char::Unstyled(c));
+        } else {
+            // Chunks of lines are colored by a thread each and written in order.
+            const CHUNK: usize = 1 << 18;
+            let mut chunks = vec![vec![]; threads];
+            let mut outs = vec![vec![]; threads];
+            loop {
+                let mut n = 0;
+                while n < threads {
+                    let chunk = &mut chunks[n];
+                    chunk.clear();
+                    // Only wait for input to start a chunk.
+                    while chunk.len() < CHUNK && (chunk.is_empty() || lines.buffered() > 0) && lines.read_into(&mut line)? {
+                        chunk.extend_from_slice(&line);
+                        chunk.push(b'\n');
                     }
-                    lines_painted.push(line_painted);
-                }
-            }
-            2 => {
-                // Boolean logic: color only if both regex filters says yes.
-                let re0 = &regexes[0];
-                let re1 = &regexes[1];
-                for line in lines {
-                    let mut line_painted: Vec<Char> = Vec::with_capacity(line.len());
-                    let mut i = 0;
-                    for m0 in re0.find_iter(&line) {
-                        for c in line[i..m0.start()].chars() {
-                            line_painted.push(Char::Unstyled(c));
-                        }
-                        i = m0.start();
-                        for m1 in re1.find_iter(m0.as_str()) {
-                            for c in line[i..m1.start()].chars() {
-                                line_painted.push(Char::Unstyled(c));
-                            }
-                            line_painted.extend(to_painted(&styles, m1.as_str()));
-                            i = m1.end();
-                        }
-                        for c in line[i..m0.end()].chars() {
-                            line_painted.push(Char::Unstyled(c));
-                        }
-                        i = m0.end();
+                    if chunk.is_empty() {
+                        break;
                     }
-                    for c in line[i..].chars() {
-                        line_painted.push(Char::Unstyled(c));
+                    n += 1;
+                    if lines.buffered() == 0 {
+                        break;
                     }
-                    lines_painted.push(line_painted);
                 }
+                if n == 0 {
+                    break;
+                }
+                std::thread::scope(|s| {
+                    let handles: Vec<_> = chunks[..n]
+                        .iter()
+                        .zip(&mut outs[..n])
+                        .map(|(chunk, out)| {
+                            s.spawn(|| {
+                                out.clear();
+                                chunk[..chunk.len() - 1].split(|&b| b == b'\n').try_for_each(|line| color_line(out, line))
+                            })
+                        })
+                        .collect();
+                    handles.into_iter().try_for_each(|handle| handle.join().unwrap())
+                })?;
+                for out in &outs[..n] {
+                    output.write_all(out)?;
+                }
+                output.flush()?;
             }
-            _ => unimplemented!(), // Unreachable
         }
-
-        if comp_consensus {
-            // Count char occurrences.
-            let mut letter_counts: Vec<HashMap<char, i32>> = Vec::with_capacity(max_line);
-            for _ in 0..max_line {
-                letter_counts.push(HashMap::new());
+        output.flush()?;
+    } else if table.is_none() && !args.transpose && !comp_consensus {
+        // Streaming one record at a time.
+        for record in records(lines).filter(selected) {
+            for line in paint_record(record).into_lines() {
+                line.write(output)?;
             }
-            for painted_line in &lines_painted {
-                for (i, ch) in painted_line.iter().enumerate() {
-                    // Only include what is styled, which will effectively apply the regex filters.
-                    match ch {
-                        Char::Unstyled(_) => {}
-                        Char::Styled(painted) => {
-                            let _letter_counts = &mut letter_counts[i];
-                            let c = painted.value;
-                            match _letter_counts.get(&c) {
-                                None => match &alphabet {
-                                    None => {
-                                        _letter_counts.insert(c, 1);
-                                    }
-                                    Some(_alphabet) => {
-                                        if _alphabet.contains(&c) {
-                                            _letter_counts.insert(c, 1);
-                                        }
-                                    }
-                                },
-                                Some(n) => {
-                                    _letter_counts.insert(c, n + 1);
-                                }
-                            };
-                        }
-                    }
+        }
+    } else if table.is_none() && !args.transpose {
+        // Streaming in passes, first counting the consensus columns, then with --weights counting
+        // again with sequence weights, then coloring one record at a time.
+        // Input that can't be read again, e.g. stdin, is spooled to a temporary file.
+        let (spool, lines) = match seekable {
+            true => (None, lines),
+            false => {
+                let mut file = BufWriter::new(tempfile::tempfile()?);
+                for line in lines {
+                    writeln!(file, "{line}")?;
                 }
+                let mut file = file.into_inner().map_err(|e| e.into_error())?;
+                file.rewind()?;
+                (Some(file.try_clone()?), Lines::from_reader(file))
             }
-            // Define consensus as string of chars seen with max occurrences at each location.
-            let mut consensus: Vec<Option<char>> = Vec::with_capacity(max_line);
-            for i in 0..max_line {
-                let mut _consensus: Option<char> = None;
-                let mut max = 0;
-                let mut tie = false;
-                for (c, n) in letter_counts[i].iter() {
-                    if *n > max {
-                        max = *n;
-                        _consensus = Some(*c);
-                        tie = false;
-                    } else if *n == max && _consensus.is_some() {
-                        tie = true;
-                    }
-                }
-                if tie {
-                    _consensus = None;
-                }
-                consensus.push(_consensus);
-            }
-
-            // Collect references to chars to highlight (consensus or mutations).
-            let highlight_consensus = args.consensus.is_some();
-            let mut painted_to_highlight = vec![];
-            for painted_line in &mut lines_painted {
-                for (i, ch) in painted_line.iter_mut().enumerate() {
-                    match consensus[i] {
-                        None => {}
-                        Some(_consensus) => match ch {
-                            Char::Unstyled(_) => {}
-                            Char::Styled(painted) => {
-                                let is_consensus = _consensus == painted.value;
-                                if is_consensus == highlight_consensus {
-                                    painted_to_highlight.push(painted);
-                                }
-                            }
-                        },
-                    }
+        };
+        let reread = || -> Result<Lines> {
+            match &spool {
+                Some(file) => {
+                    let mut file = file.try_clone()?;
+                    file.rewind()?;
+                    Ok(Lines::from_reader(file))
                 }
+                None => Ok(read_lines(args.files.clone(), threads)?.strip_ansi(!keep_ansi)),
             }
+        };
+        let counted = |record: &Record<String>| count_all || selected(record);
 
-            // Apply either an attribute or bg color to highlighted chars.
-            let s_style = args.consensus.as_ref().or(args.mutations.as_ref()).unwrap();
-            match s_style.as_str() {
-                "bold" => {
-                    for painted in painted_to_highlight {
-                        painted.style = painted.style.bold();
-                    }
-                }
-                "underline" => {
-                    for painted in painted_to_highlight {
-                        painted.style = painted.style.underline();
-                    }
+        quiet.set(true);
+        let mut counter = Counter::new(&consensus_opts, threads);
+        for record in records(lines).filter(counted) {
+            let group = groups.group(&record);
+            counter.push(group, paint_record(record).into_lines(), 1.0);
+        }
+        let mut counts = counter.finish();
+        if consensus_opts.henikoff {
+            n_records.set(0);
+            let mut counter = Counter::new(&consensus_opts, threads);
+            for record in records(reread()?).filter(counted) {
+                let group = groups.group(&record);
+                let lines: Vec<Line> = paint_record(record).into_lines().collect();
+                let weight = counts[group].henikoff(&lines, &consensus_opts);
+                counter.push(group, lines, weight);
+            }
+            counts = counter.finish();
+        }
+        quiet.set(false);
+        n_records.set(0);
+
+        let consensuses: Vec<Vec<Option<Column>>> = counts.iter().map(|counts| counts.consensus(&consensus_opts)).collect();
+        let hl = consensus_hl.unwrap();
+        for record in records(reread()?).filter(selected) {
+            // Groups have the same indices as in the first pass.
+            let group = groups.group(&record);
+            let mut lines: Vec<Line> = paint_record(record).into_lines().collect();
+            if let Some(consensus) = consensuses.get(group) {
+                highlight(&mut lines, consensus, &consensus_opts, args.consensus.is_some(), hl);
+            }
+            for line in &lines {
+                line.write(output)?;
+            }
+        }
+        if groups.is_grouped() {
+            differing(&consensuses).write(output)?;
+        }
+    } else {
+        // Not streaming.
+        // First read input into memory and gather styles according to each char in each line.
+        // Lines of each record along with its group and whether it is selected, where unselected
+        // records are only counted for consensus. Table rows are a single record.
+        let mut painted: Vec<(usize, bool, Vec<Line>)> = vec![];
+        if let Some(table) = &table {
+            let mut rows = vec![table.header_fields()];
+            rows.extend(lines.map(|line| table.fields(&line, paint_cell)));
+            // Consensus is by char index so columns need to line up.
+            let widths = if args.table_align || comp_consensus {
+                table.align_quotes(&mut rows);
+                widths(&rows)
+            } else {
+                vec![]
+            };
+            painted.push((0, true, rows.into_iter().map(|row| table.join(row, &widths)).collect()));
+        } else {
+            for record in records(lines) {
+                let group = groups.group(&record);
+                let is_selected = selected(&record);
+                if is_selected || count_all {
+                    painted.push((group, is_selected, paint_record(record).into_lines().collect()));
                 }
-                color => {
-                    let col = parse_color(color).expect(color);
-                    for painted in painted_to_highlight {
-                        painted.style = painted.style.bg(col);
-                    }
+            }
+        }
+        let mut lines_painted: Vec<Line> = vec![];
+        if let Some(hl) = consensus_hl {
+            let mut consensuses = vec![];
+            for group in 0..groups.len().max(1) {
+                let in_group: Vec<&Vec<Line>> = painted.iter().filter(|(g, _, _)| *g == group).map(|(_, _, lines)| lines).collect();
+                let lines: Vec<&Line> = in_group.iter().flat_map(|lines| lines.iter()).collect();
+                let mut counts = Counts::new();
+                counts.add(&lines, &vec![1.0; lines.len()], &consensus_opts, threads);
+                if consensus_opts.henikoff {
+                    let weights: Vec<f64> = in_group
+                        .iter()
+                        .flat_map(|lines| {
+                            let weight = counts.henikoff(lines, &consensus_opts);
+                            std::iter::repeat_n(weight, lines.len())
+                        })
+                        .collect();
+                    counts = Counts::new();
+                    counts.add(&lines, &weights, &consensus_opts, threads);
                 }
-            };
+                consensuses.push(counts.consensus(&consensus_opts));
+            }
+            for (group, _, lines) in &mut painted {
+                highlight(lines, &consensuses[*group], &consensus_opts, args.consensus.is_some(), hl);
+            }
+            if groups.is_grouped() {
+                painted.push((0, true, vec![differing(&consensuses)]));
+            }
+        }
+        for (_, is_selected, lines) in painted {
+            if is_selected {
+                lines_painted.extend(lines);
+            }
         }
 
         if !args.transpose {
             for painted_line in &lines_painted {
-                for ch in painted_line {
-                    ch.write(output)?;
-                }
-                output.write(&newline)?;
+                painted_line.write(output)?;
             }
         } else {
-            // Transpose.
-            for j in 0..max_line {
-                for painted_line in &lines_painted {
-                    match painted_line.get(j) {
-                        None => output.write(&space)?,
-                        Some(ch) => ch.write(output)?,
-                    };
-                }
-                output.write(&newline)?;
-            }
+            write_transposed(output, &lines_painted)?;
         }
     }
 
     // Flush output
     output.flush()?;
+    close_pager(pager_stdin, pager_child)
+}
 
-    // Drop the pager stdin to signal EOF, then wait for pager
+// Drop the pager stdin to signal EOF, then wait for pager
+fn close_pager(pager_stdin: Option<std::process::ChildStdin>, pager_child: Option<Child>) -> Result<()> {
     drop(pager_stdin);
     if let Some(mut child) = pager_child {
         let _ = child.wait();
     }
-
     Ok(())
 }
@@ -0,0 +1,212 @@
+// Progressive multiple alignment: a UPGMA guide tree from k-mer distances, then profile-profile
+// alignment up the tree. Fast rather than optimal, meant for up to a few hundred sequences.
+use anyhow::Result;
+
+use crate::align::{Mode, N_RESIDUES, Scoring, Step, gotoh};
+use crate::bio::{is_gap, is_nucleotide};
+use crate::records::Record;
+
+// Size of the intersection of sorted multisets.
+fn shared(a: &[u64], b: &[u64]) -> usize {
+    let (mut i, mut j, mut n) = (0, 0, 0);
+    while i < a.len() && j < b.len() {
+        match a[i].cmp(&b[j]) {
+            std::cmp::Ordering::Less => i += 1,
+            std::cmp::Ordering::Greater => j += 1,
+            std::cmp::Ordering::Equal => {
+                n += 1;
+                i += 1;
+                j += 1;
+            }
+        }
+    }
+    n
+}
+
+// Fraction of the k-mers of the shorter sequence that aren't shared, for each pair of sequences
+// given as residue indexes.
+fn kmer_distances(seqs: &[Vec<usize>], k: usize) -> Vec<Vec<f64>> {
+    let kmers: Vec<Vec<u64>> = seqs
+        .iter()
+        .map(|seq| {
+            let mut kmers: Vec<u64> =
+                seq.windows(k).map(|w| w.iter().fold(0, |code, &r| code * N_RESIDUES as u64 + r as u64)).collect();
+            kmers.sort_unstable();
+            kmers
+        })
+        .collect();
+    let n = seqs.len();
+    let mut dist = vec![vec![0.0; n]; n];
+    for i in 0..n {
+        for j in 0..i {
+            let total = kmers[i].len().min(kmers[j].len());
+            let d = match total {
+                0 => 1.0,
+                _ => 1.0 - shared(&kmers[i], &kmers[j]) as f64 / total as f64,
+            };
+            dist[i][j] = d;
+            dist[j][i] = d;
+        }
+    }
+    dist
+}
+
+// The merges of a UPGMA tree as pairs of clusters, where leaves are 0..n and merge i makes cluster n + i.
+fn upgma(mut dist: Vec<Vec<f64>>) -> Vec<(usize, usize)> {
+    let n = dist.len();
+    // Cluster and its size for each row of the distances that hasn't been merged away.
+    let mut clusters: Vec<Option<(usize, usize)>> = (0..n).map(|i| Some((i, 1))).collect();
+    let mut merges = vec![];
+    for cluster in n..(2 * n).saturating_sub(1) {
+        let mut closest = (f64::INFINITY, 0, 0);
+        for i in 0..n {
+            if clusters[i].is_none() {
+                continue;
+            }
+            for j in 0..i {
+                if clusters[j].is_some() && dist[i][j] < closest.0 {
+                    closest = (dist[i][j], i, j);
+                }
+            }
+        }
+        let (_, i, j) = closest;
+        let ((ci, size_i), (cj, size_j)) = (clusters[i].unwrap(), clusters[j].unwrap());
+        merges.push((cj, ci));
+        for k in 0..n {
+            if k != i && k != j && clusters[k].is_some() {
+                let d = (dist[i][k] * size_i as f64 + dist[j][k] * size_j as f64) / (size_i + size_j) as f64;
+                dist[i][k] = d;
+                dist[k][i] = d;
+            }
+        }
+        clusters[i] = Some((cluster, size_i + size_j));
+        clusters[j] = None;
+    }
+    merges
+}
+
+// Aligned rows of a subset of the sequences.
+struct Profile {
+    members: Vec<usize>,
+    rows: Vec<Vec<char>>,
+}
+
+impl Profile {
+    fn len(&self) -> usize {
+        self.rows.first().map_or(0, |row| row.len())
+    }
+
+    // The residues of each column as (residue index, fraction of rows). Gaps count as no residue,
+    // so gappy columns score less.
+    fn frequencies(&self, scoring: &Scoring) -> Vec<Vec<(usize, f32)>> {
+        let n = self.rows.len() as f32;
+        (0..self.len())
+            .map(|col| {
+                let mut counts = [0.0f32; N_RESIDUES];
+                for row in &self.rows {
+                    if !is_gap(row[col]) {
+                        counts[scoring.index(row[col])] += 1.0;
+                    }
+                }
+                counts.iter().enumerate().filter(|&(_, &c)| c > 0.0).map(|(r, &c)| (r, c / n)).collect()
+            })
+            .collect()
+    }
+}
+
+// Globally align two profiles scoring columns by the average substitution score of their residue pairs.
+fn align_profiles(a: Profile, b: Profile, scoring: &Scoring, table: &[[f32; N_RESIDUES]]) -> Result<Profile> {
+    // The expected score of each column of a against each residue.
+    let expected: Vec<f32> = a
+        .frequencies(scoring)
+        .iter()
+        .flat_map(|col| (0..N_RESIDUES).map(|y| col.iter().map(|&(x, f)| f * table[x][y]).sum()))
+        .collect();
+    let freq_b = b.frequencies(scoring);
+    let score = |i: usize, j: usize| {
+        let expected = &expected[i * N_RESIDUES..(i + 1) * N_RESIDUES];
+        freq_b[j].iter().map(|&(y, f)| f * expected[y]).sum()
+    };
+    let (steps, ..) = gotoh(a.len(), b.len(), score, scoring.gap_open as f32, scoring.gap_extend as f32, Mode::Global)?;
+
+    let mut rows = vec![Vec::with_capacity(steps.len()); a.rows.len() + b.rows.len()];
+    let (rows_a, rows_b) = rows.split_at_mut(a.rows.len());
+    let (mut i, mut j) = (0, 0);
+    for step in steps {
+        let (col_a, col_b) = match step {
+            Step::Match => (Some(i), Some(j)),
+            Step::GapB => (Some(i), None),
+            Step::GapA => (None, Some(j)),
+        };
+        for (row, src) in rows_a.iter_mut().zip(&a.rows) {
+            row.push(col_a.map_or('-', |i| src[i]));
+        }
+        for (row, src) in rows_b.iter_mut().zip(&b.rows) {
+            row.push(col_b.map_or('-', |j| src[j]));
+        }
+        i += col_a.is_some() as usize;
+        j += col_b.is_some() as usize;
+    }
+    let mut members = a.members;
+    members.extend(b.members);
+    Ok(Profile { members, rows })
+}
+
+// Align sequences given without gaps, returning the aligned rows in the same order.
+pub fn align_sequences(seqs: &[Vec<char>], scoring: &Scoring) -> Result<Vec<Vec<char>>> {
+    let indexes: Vec<Vec<usize>> = seqs.iter().map(|seq| seq.iter().map(|&c| scoring.index(c)).collect()).collect();
+    // Longer k-mers for the smaller nucleotide alphabet.
+    let k = if scoring.blosum { 3 } else { 6 };
+    let merges = upgma(kmer_distances(&indexes, k));
+    let table = scoring.table();
+    let mut profiles: Vec<Option<Profile>> =
+        seqs.iter().enumerate().map(|(i, seq)| Some(Profile { members: vec![i], rows: vec![seq.clone()] })).collect();
+    for (a, b) in merges {
+        let (a, b) = (profiles[a].take().unwrap(), profiles[b].take().unwrap());
+        profiles.push(Some(align_profiles(a, b, scoring, &table)?));
+    }
+    let mut aligned = vec![vec![]; seqs.len()];
+    if let Some(Some(root)) = profiles.pop() {
+        for (i, row) in root.members.into_iter().zip(root.rows) {
+            aligned[i] = row;
+        }
+    }
+    Ok(aligned)
+}
+
+// Align the sequences of records and write them as fasta with one line per sequence. Lines
+// without a header are aligned as sequences of their own, except empty lines which are kept.
+pub fn align_records(records: Vec<Record<String>>) -> Result<Vec<String>> {
+    let is_seq = |record: &Record<String>| record.header.is_some() || record.seq.iter().any(|l| !l.trim().is_empty());
+    let seqs: Vec<Vec<char>> = records
+        .iter()
+        .filter(|record| is_seq(record))
+        .map(|record| {
+            record
+                .seq
+                .iter()
+                .flat_map(|line| line.chars())
+                .filter(|&c| if record.blocks { c.is_alphabetic() } else { !c.is_whitespace() && !is_gap(c) })
+                .collect()
+        })
+        .collect();
+    let residues: Vec<char> = seqs.iter().flatten().copied().collect();
+    let scoring = Scoring::new(!is_nucleotide(&residues));
+    let mut aligned = align_sequences(&seqs, &scoring)?.into_iter();
+    let mut lines = vec![];
+    for record in records {
+        if !is_seq(&record) {
+            lines.extend(record.into_lines());
+            continue;
+        }
+        match &record.header {
+            None => {}
+            Some(header) if header.starts_with('>') => lines.push(header.clone()),
+            // Fastq, GenBank, and EMBL records become fasta records.
+            Some(_) if record.blocks => lines.push(format!(">{}", record.id().unwrap_or_default())),
+            Some(header) => lines.push(format!(">{}", &header[1..])),
+        }
+        lines.push(aligned.next().unwrap_or_default().into_iter().collect());
+    }
+    Ok(lines)
+}
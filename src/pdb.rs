@@ -0,0 +1,364 @@
+// Sequences of chains in PDB and mmCIF files, and coloring by B-factor or pLDDT.
+use anyhow::{Result, bail};
+use std::collections::HashMap;
+use yansi::Color::{self, *};
+
+use crate::ansi_colors::{is_light, supported};
+use crate::bio::{RESIDUE_CODES, is_gap};
+use crate::render::{Line, restyle, sequence};
+
+#[derive(Clone, Copy, PartialEq)]
+pub enum Format {
+    Pdb,
+    Mmcif,
+}
+
+impl Format {
+    // PDB or mmCIF by file extension. Content isn't sniffed, since e.g. a first line starting with
+    // "HEADER" or "TITLE" may as well be plain text.
+    pub fn from_path(path: &str) -> Option<Self> {
+        let ext = std::path::Path::new(path).extension()?.to_str()?.to_lowercase();
+        match ext.as_str() {
+            "pdb" | "ent" => Some(Format::Pdb),
+            "cif" | "mmcif" => Some(Format::Mmcif),
+            _ => None,
+        }
+    }
+}
+
+// Which residues make up the sequence of a chain.
+#[derive(Clone, Copy, PartialEq)]
+pub enum Source {
+    // The full sequence of the construct from SEQRES or _pdbx_poly_seq_scheme, falling back to
+    // observed residues if not given.
+    Seqres,
+    // Residues with coordinates in the first model.
+    Observed,
+}
+
+impl Source {
+    pub fn parse(s: &str) -> Result<Self> {
+        match s.to_lowercase().as_str() {
+            "seqres" => Ok(Source::Seqres),
+            "observed" | "atom" => Ok(Source::Observed),
+            _ => bail!("Sequence source not understood: '{s}'. Use \"seqres\" or \"observed\"."),
+        }
+    }
+}
+
+pub struct Chain {
+    // Entry ID and chain ID, e.g. "1ABC_A", or only the chain ID if the entry has no ID.
+    pub name: String,
+    pub seq: Vec<char>,
+    // Mean B-factor of the atoms of each residue, which is pLDDT for AlphaFold models.
+    // Empty for SEQRES sequences.
+    pub values: Vec<f32>,
+}
+
+// Gathers residues from atoms in the order they appear.
+#[derive(Default)]
+struct Observed {
+    chains: Vec<Chain>,
+    // Number of atoms of each residue of each chain.
+    counts: Vec<Vec<usize>>,
+    // Chain and residue number with insertion code of the last atom.
+    last: Option<(String, String)>,
+    current: usize,
+}
+
+impl Observed {
+    fn atom(&mut self, chain: &str, residue: &str, code: char, value: f32) {
+        let key = (chain.to_string(), residue.to_string());
+        if self.last.as_ref() != Some(&key) {
+            self.current = match self.chains.iter().position(|c| c.name == chain) {
+                Some(i) => i,
+                None => {
+                    self.chains.push(Chain { name: chain.to_string(), seq: vec![], values: vec![] });
+                    self.counts.push(vec![]);
+                    self.chains.len() - 1
+                }
+            };
+            self.chains[self.current].seq.push(code);
+            self.chains[self.current].values.push(0.);
+            self.counts[self.current].push(0);
+            self.last = Some(key);
+        }
+        *self.chains[self.current].values.last_mut().unwrap() += value;
+        *self.counts[self.current].last_mut().unwrap() += 1;
+    }
+
+    fn finish(mut self) -> Vec<Chain> {
+        for (chain, counts) in self.chains.iter_mut().zip(&self.counts) {
+            for (value, &n) in chain.values.iter_mut().zip(counts) {
+                *value /= n as f32;
+            }
+        }
+        self.chains
+    }
+}
+
+fn add_seqres(chains: &mut Vec<Chain>, chain: &str, residue: &str) {
+    let code = RESIDUE_CODES.get(residue).copied().unwrap_or('X');
+    match chains.iter_mut().find(|c| c.name == chain) {
+        Some(c) => c.seq.push(code),
+        None => chains.push(Chain { name: chain.to_string(), seq: vec![code], values: vec![] }),
+    }
+}
+
+// Residue code of an atom. Unknown residues are X for ATOM and skipped for HETATM, e.g. ligands
+// and water.
+fn residue_code(residue: &str, hetero: bool) -> Option<char> {
+    match RESIDUE_CODES.get(residue) {
+        Some(&code) => Some(code),
+        None if hetero => None,
+        None => Some('X'),
+    }
+}
+
+fn read_pdb(lines: impl Iterator<Item = String>) -> (String, Vec<Chain>, Observed) {
+    let mut id = String::new();
+    let mut seqres = vec![];
+    let mut observed = Observed::default();
+    for line in lines {
+        let field = |start: usize, end: usize| line.get(start..end.min(line.len())).unwrap_or("").trim();
+        match line.get(..6).unwrap_or(&line) {
+            "HEADER" => id = field(62, 66).to_string(),
+            "SEQRES" => {
+                for residue in field(19, line.len()).split_whitespace() {
+                    add_seqres(&mut seqres, field(11, 12), residue);
+                }
+            }
+            record @ ("ATOM  " | "HETATM") => {
+                let Some(code) = residue_code(field(17, 20), record == "HETATM") else {
+                    continue;
+                };
+                let value = field(60, 66).parse().unwrap_or(0.);
+                observed.atom(field(21, 22), field(22, 27), code, value);
+            }
+            // Only the first model.
+            "ENDMDL" => break,
+            _ => {}
+        }
+    }
+    (id, seqres, observed)
+}
+
+// Split mmCIF lines into tokens, where quoted strings and multi-line text fields are one token.
+fn cif_tokens(lines: impl Iterator<Item = String>) -> Vec<String> {
+    let mut tokens = vec![];
+    let mut text: Option<String> = None;
+    for line in lines {
+        if let Some(rest) = line.strip_prefix(';') {
+            match text.take() {
+                Some(t) => tokens.push(t),
+                None => text = Some(rest.to_string()),
+            }
+            continue;
+        }
+        if let Some(t) = &mut text {
+            t.push_str(&line);
+            continue;
+        }
+        let mut chars = line.char_indices().peekable();
+        while let Some((i, c)) = chars.next() {
+            if c.is_whitespace() {
+                continue;
+            }
+            if c == '#' {
+                break;
+            }
+            let mut end = line.len();
+            if c == '\'' || c == '"' {
+                // A quote only ends a token if followed by whitespace.
+                while let Some((j, d)) = chars.next() {
+                    if d == c && chars.peek().is_none_or(|(_, e)| e.is_whitespace()) {
+                        end = j;
+                        break;
+                    }
+                }
+                tokens.push(line[i + 1..end].to_string());
+            } else {
+                while let Some(&(j, d)) = chars.peek() {
+                    if d.is_whitespace() {
+                        end = j;
+                        break;
+                    }
+                    chars.next();
+                }
+                tokens.push(line[i..end].to_string());
+            }
+        }
+    }
+    tokens
+}
+
+// Values of each mmCIF tag, where tags outside loops have a single value.
+fn read_cif(lines: impl Iterator<Item = String>) -> HashMap<String, Vec<String>> {
+    let tokens = cif_tokens(lines);
+    let mut items: HashMap<String, Vec<String>> = HashMap::new();
+    let mut i = 0;
+    while i < tokens.len() {
+        let token = &tokens[i];
+        if token == "loop_" {
+            i += 1;
+            let mut tags = vec![];
+            while i < tokens.len() && tokens[i].starts_with('_') {
+                tags.push(tokens[i].clone());
+                i += 1;
+            }
+            let mut k = 0;
+            while i < tokens.len() && !tokens[i].starts_with('_') && tokens[i] != "loop_" && !tokens[i].starts_with("data_") {
+                // Values of a loop without tags belong to nothing and are skipped.
+                if !tags.is_empty() {
+                    items.entry(tags[k % tags.len()].clone()).or_default().push(tokens[i].clone());
+                }
+                k += 1;
+                i += 1;
+            }
+        } else if let Some(data) = token.strip_prefix("data_") {
+            items.insert("data_".to_string(), vec![data.to_string()]);
+            i += 1;
+        } else if token.starts_with('_') && i + 1 < tokens.len() {
+            items.insert(token.clone(), vec![tokens[i + 1].clone()]);
+            i += 2;
+        } else {
+            i += 1;
+        }
+    }
+    items
+}
+
+fn read_mmcif(lines: impl Iterator<Item = String>) -> (String, Vec<Chain>, Observed) {
+    let items = read_cif(lines);
+    let column = |tag: &str| items.get(tag).map(|v| v.as_slice()).unwrap_or(&[]);
+    // Author chain IDs and residue numbers as in PDB files, falling back to label ones.
+    let column_or = |tag: &str, fallback: &str| match column(tag) {
+        [] => column(fallback),
+        values => values,
+    };
+    let id = column("_entry.id").first().or(column("data_").first()).cloned().unwrap_or_default();
+
+    let mut seqres = vec![];
+    let strands = column_or("_pdbx_poly_seq_scheme.pdb_strand_id", "_pdbx_poly_seq_scheme.asym_id");
+    for (chain, residue) in strands.iter().zip(column("_pdbx_poly_seq_scheme.mon_id")) {
+        add_seqres(&mut seqres, chain, residue);
+    }
+
+    let mut observed = Observed::default();
+    let groups = column("_atom_site.group_PDB");
+    let residues = column_or("_atom_site.auth_comp_id", "_atom_site.label_comp_id");
+    let chains = column_or("_atom_site.auth_asym_id", "_atom_site.label_asym_id");
+    let numbers = column_or("_atom_site.auth_seq_id", "_atom_site.label_seq_id");
+    let insertions = column("_atom_site.pdbx_PDB_ins_code");
+    let values = column("_atom_site.B_iso_or_equiv");
+    let models = column("_atom_site.pdbx_PDB_model_num");
+    for i in 0..groups.len().min(residues.len()).min(chains.len()).min(numbers.len()) {
+        if models.get(i).is_some_and(|m| Some(m) != models.first()) {
+            break;
+        }
+        let Some(code) = residue_code(&residues[i], groups[i] == "HETATM") else {
+            continue;
+        };
+        let insertion = insertions.get(i).map_or("", |s| s.trim_matches(['?', '.']));
+        let value = values.get(i).and_then(|v| v.parse().ok()).unwrap_or(0.);
+        observed.atom(&chains[i], &format!("{}{insertion}", numbers[i]), code, value);
+    }
+    (id, seqres, observed)
+}
+
+// Read the sequence of each chain of the first entry in PDB or mmCIF lines.
+pub fn read_chains(lines: impl Iterator<Item = String>, format: Format, source: Source) -> Vec<Chain> {
+    let (id, seqres, observed) = match format {
+        Format::Pdb => read_pdb(lines),
+        Format::Mmcif => read_mmcif(lines),
+    };
+    let mut chains = match source {
+        Source::Seqres if !seqres.is_empty() => seqres,
+        _ => observed.finish(),
+    };
+    if !id.is_empty() {
+        for chain in &mut chains {
+            chain.name = format!("{id}_{}", chain.name);
+        }
+    }
+    chains
+}
+
+// How to map B-factors to colors.
+#[derive(Clone, Copy)]
+pub enum Scale {
+    // Blue through white to red from the lowest to the highest value of each chain.
+    Range,
+    // AlphaFold confidence colors from orange (very low) through yellow and light blue to dark
+    // blue (very high) on a fixed scale from 0 to 100.
+    Plddt,
+}
+
+impl Scale {
+    pub fn parse(s: &str) -> Result<Self> {
+        match s.to_lowercase().as_str() {
+            "range" | "bfactor" => Ok(Scale::Range),
+            "plddt" => Ok(Scale::Plddt),
+            _ => bail!("B-factor scale not understood: '{s}'. Use \"range\" or \"plddt\"."),
+        }
+    }
+
+    // Color stops as (value, color) in increasing order of value.
+    fn stops(self, min: f32, max: f32) -> Vec<(f32, Color)> {
+        match self {
+            Scale::Range => vec![
+                (min, Rgb(0, 0, 255)),
+                ((min + max) / 2., Rgb(255, 255, 255)),
+                (max, Rgb(255, 0, 0)),
+            ],
+            // Middle of each of the confidence bins <50, 50-70, 70-90, and >90.
+            Scale::Plddt => vec![
+                (40., Rgb(255, 125, 69)),
+                (60., Rgb(255, 219, 19)),
+                (80., Rgb(101, 203, 243)),
+                (95., Rgb(0, 83, 214)),
+            ],
+        }
+    }
+}
+
+fn gradient(stops: &[(f32, Color)], value: f32) -> Color {
+    let rgb = |col: Color| match col {
+        Rgb(r, g, b) => (r as f32, g as f32, b as f32),
+        _ => (0., 0., 0.),
+    };
+    let Some(k) = stops.iter().position(|&(v, _)| value < v) else {
+        return stops[stops.len() - 1].1;
+    };
+    if k == 0 {
+        return stops[0].1;
+    }
+    let ((v0, c0), (v1, c1)) = (stops[k - 1], stops[k]);
+    let t = (value - v0) / (v1 - v0);
+    let ((r0, g0, b0), (r1, g1, b1)) = (rgb(c0), rgb(c1));
+    let mix = |a: f32, b: f32| (a + t * (b - a)).round() as u8;
+    Rgb(mix(r0, r1), mix(g0, g1), mix(b0, b1))
+}
+
+pub struct BfactorOpts {
+    // Value of each residue keyed by chain name.
+    pub values: HashMap<String, Vec<f32>>,
+    pub scale: Scale,
+}
+
+// Set the background of each residue of a chain given by its name to a color by its B-factor.
+pub fn paint_bfactors(seq: &mut [Line], id: &str, opts: &BfactorOpts) {
+    let Some(values) = opts.values.get(id).filter(|v| !v.is_empty()) else {
+        return;
+    };
+    let min = values.iter().copied().fold(f32::INFINITY, f32::min);
+    let max = values.iter().copied().fold(f32::NEG_INFINITY, f32::max);
+    let stops = opts.scale.stops(min, max);
+    let (positions, chars) = sequence(seq);
+    let residues = (0..chars.len()).filter(|&k| !is_gap(chars[k]));
+    for (k, &value) in residues.zip(values) {
+        let bg = gradient(&stops, value);
+        let fg = if is_light(bg) { Black } else { White };
+        restyle(seq, positions[k], |style| style.bg(supported(bg)).fg(fg));
+    }
+}
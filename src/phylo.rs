@@ -0,0 +1,246 @@
+// Alignment formats used in phylogenetics, PHYLIP and NEXUS.
+use anyhow::{Result, bail};
+use std::io::Write;
+
+use crate::bio::is_nucleotide;
+
+#[derive(Clone, Copy, PartialEq)]
+pub enum Format {
+    Fasta,
+    // Relaxed PHYLIP where names are separated from sequences by whitespace.
+    Phylip,
+    // Strict PHYLIP where names are the first 10 chars.
+    PhylipStrict,
+    Nexus,
+}
+
+impl Format {
+    pub fn parse(s: &str) -> Result<Self> {
+        match s.to_lowercase().as_str() {
+            "fasta" => Ok(Format::Fasta),
+            "phylip" | "phylip-relaxed" => Ok(Format::Phylip),
+            "phylip-strict" => Ok(Format::PhylipStrict),
+            "nexus" => Ok(Format::Nexus),
+            _ => bail!("Format not understood: '{s}'. Use \"fasta\", \"phylip\", \"phylip-strict\", or \"nexus\"."),
+        }
+    }
+
+    // PHYLIP or NEXUS by file extension. Content isn't sniffed, since e.g. a first line of two
+    // numbers is as likely to be a table.
+    pub fn from_path(path: &str) -> Option<Self> {
+        let ext = std::path::Path::new(path).extension()?.to_str()?.to_lowercase();
+        match ext.as_str() {
+            "phy" | "phylip" => Some(Format::Phylip),
+            "nex" | "nexus" | "nxs" => Some(Format::Nexus),
+            _ => None,
+        }
+    }
+}
+
+// Try to read PHYLIP with the given name layout as sequential or interleaved.
+fn read_phylip_as(lines: &[&str], ntax: usize, nchar: usize, strict: bool, interleaved: bool) -> Option<Vec<(String, String)>> {
+    let split = |line: &str| -> Option<(String, String)> {
+        let (name, seq) = if strict {
+            (line.get(..10).unwrap_or(line), line.get(10..).unwrap_or(""))
+        } else {
+            line.trim_start().split_once(char::is_whitespace)?
+        };
+        Some((name.trim().to_string(), seq.split_whitespace().collect()))
+    };
+    let mut seqs: Vec<(String, String)> = vec![];
+    let mut lines = lines.iter();
+    if interleaved {
+        for _ in 0..ntax {
+            seqs.push(split(lines.next()?)?);
+        }
+        for (k, line) in lines.enumerate() {
+            seqs[k % ntax].1.extend(line.split_whitespace());
+        }
+    } else {
+        for _ in 0..ntax {
+            let mut entry = split(lines.next()?)?;
+            while entry.1.chars().count() < nchar {
+                entry.1.extend(lines.next()?.split_whitespace());
+            }
+            seqs.push(entry);
+        }
+        if lines.next().is_some() {
+            return None;
+        }
+    }
+    seqs.iter().all(|(_, seq)| seq.chars().count() == nchar).then_some(seqs)
+}
+
+// Read relaxed or strict PHYLIP, sequential or interleaved, as (name, sequence) pairs.
+pub fn read_phylip(lines: impl Iterator<Item = String>) -> Result<Vec<(String, String)>> {
+    let lines: Vec<String> = lines.collect();
+    let mut lines = lines.iter().map(|l| l.as_str()).filter(|l| !l.trim().is_empty());
+    let header = lines.next().unwrap_or("");
+    let dims: Vec<usize> = header.split_whitespace().filter_map(|f| f.parse().ok()).collect();
+    let &[ntax, nchar] = dims.as_slice() else {
+        bail!("PHYLIP header needs the number of taxa and characters: {header}");
+    };
+    let lines: Vec<&str> = lines.collect();
+    for (strict, interleaved) in [(false, false), (false, true), (true, false), (true, true)] {
+        if let Some(seqs) = read_phylip_as(&lines, ntax, nchar, strict, interleaved) {
+            return Ok(seqs);
+        }
+    }
+    bail!("PHYLIP alignment does not have {ntax} sequences of length {nchar}.")
+}
+
+// Remove NEXUS comments in square brackets, which may span lines.
+fn strip_comments(line: &str, in_comment: &mut bool) -> String {
+    let mut stripped = String::with_capacity(line.len());
+    for c in line.chars() {
+        match c {
+            '[' => *in_comment = true,
+            ']' => *in_comment = false,
+            c if !*in_comment => stripped.push(c),
+            _ => {}
+        }
+    }
+    stripped
+}
+
+// Read the MATRIX of a DATA or CHARACTERS block of a NEXUS file as (name, sequence) pairs.
+// Interleaved matrices are joined by name. Spaces in quoted names become underscores.
+pub fn read_nexus(lines: impl Iterator<Item = String>) -> Result<Vec<(String, String)>> {
+    let mut seqs: Vec<(String, String)> = vec![];
+    let mut in_matrix = false;
+    let mut in_comment = false;
+    for line in lines {
+        let line = strip_comments(&line, &mut in_comment);
+        let line = line.trim();
+        if !in_matrix {
+            in_matrix = line.eq_ignore_ascii_case("matrix");
+            continue;
+        }
+        let (line, done) = match line.split_once(';') {
+            Some((line, _)) => (line, true),
+            None => (line, false),
+        };
+        let (name, seq) = match line.strip_prefix('\'') {
+            Some(quoted) => quoted.split_once('\'').unwrap_or((quoted, "")),
+            None => line.split_once(char::is_whitespace).unwrap_or((line, "")),
+        };
+        if !name.is_empty() {
+            let name = name.replace(' ', "_");
+            let seq = seq.split_whitespace();
+            match seqs.iter_mut().find(|(n, _)| *n == name) {
+                Some((_, s)) => s.extend(seq),
+                None => seqs.push((name, seq.collect())),
+            }
+        }
+        if done {
+            return Ok(seqs);
+        }
+    }
+    if seqs.is_empty() {
+        bail!("No MATRIX found in NEXUS file.");
+    }
+    Ok(seqs)
+}
+
+// Quote a NEXUS name if it has whitespace or punctuation.
+fn nexus_name(name: &str) -> String {
+    if name.chars().all(|c| c.is_alphanumeric() || "_.-|".contains(c)) {
+        name.to_string()
+    } else {
+        format!("'{}'", name.replace('\'', "''"))
+    }
+}
+
+// Write sequences without color in the given format. PHYLIP and NEXUS need sequences of equal length.
+pub fn write_alignment(buf: &mut (impl Write + ?Sized), format: Format, seqs: &[(String, String)]) -> Result<()> {
+    let nchar = seqs.first().map_or(0, |(_, seq)| seq.chars().count());
+    if format != Format::Fasta && seqs.iter().any(|(_, seq)| seq.chars().count() != nchar) {
+        bail!("Sequences need equal length to write PHYLIP or NEXUS.");
+    }
+    match format {
+        Format::Fasta => {
+            for (name, seq) in seqs {
+                writeln!(buf, ">{name}\n{seq}")?;
+            }
+        }
+        Format::Phylip | Format::PhylipStrict => {
+            writeln!(buf, "{} {nchar}", seqs.len())?;
+            let width = seqs.iter().map(|(name, _)| name.chars().count()).max().unwrap_or(0);
+            for (name, seq) in seqs {
+                if format == Format::PhylipStrict {
+                    let name: String = name.chars().take(10).collect();
+                    writeln!(buf, "{name:<10}{seq}")?;
+                } else {
+                    writeln!(buf, "{name:<width$} {seq}")?;
+                }
+            }
+        }
+        Format::Nexus => {
+            let chars: Vec<char> = seqs.iter().flat_map(|(_, seq)| seq.chars()).collect();
+            let datatype = if is_nucleotide(&chars) { "DNA" } else { "PROTEIN" };
+            let names: Vec<String> = seqs.iter().map(|(name, _)| nexus_name(name)).collect();
+            let width = names.iter().map(|name| name.chars().count()).max().unwrap_or(0);
+            writeln!(buf, "#NEXUS\n\nBEGIN DATA;")?;
+            writeln!(buf, "\tDIMENSIONS NTAX={} NCHAR={nchar};", seqs.len())?;
+            writeln!(buf, "\tFORMAT DATATYPE={datatype} MISSING=? GAP=-;")?;
+            writeln!(buf, "\tMATRIX")?;
+            for (name, (_, seq)) in names.iter().zip(seqs) {
+                writeln!(buf, "\t{name:<width$} {seq}")?;
+            }
+            writeln!(buf, "\t;\nEND;")?;
+        }
+    }
+    Ok(())
+}
+
+// Leaf names of a Newick tree in order. Quoted names keep their spaces as underscores, as for NEXUS.
+pub fn read_newick_leaves(text: &str) -> Vec<String> {
+    let mut leaves = vec![];
+    let mut chars = text.chars().peekable();
+    // Whether the next name is a leaf rather than an internal node label.
+    let mut leaf = true;
+    while let Some(c) = chars.next() {
+        match c {
+            '(' | ',' => leaf = true,
+            ')' => leaf = false,
+            // Comments.
+            '[' => {
+                for d in chars.by_ref() {
+                    if d == ']' {
+                        break;
+                    }
+                }
+            }
+            // Branch lengths.
+            ':' => {
+                while chars.peek().is_some_and(|&d| !"(),;[".contains(d)) {
+                    chars.next();
+                }
+            }
+            ';' => break,
+            c if c.is_whitespace() => {}
+            c => {
+                let mut name = String::new();
+                if c == '\'' {
+                    while let Some(d) = chars.next() {
+                        if d == '\'' && chars.next_if_eq(&'\'').is_none() {
+                            break;
+                        }
+                        name.push(d);
+                    }
+                    name = name.replace(' ', "_");
+                } else {
+                    name.push(c);
+                    while let Some(d) = chars.next_if(|&d| !"(),:;[".contains(d) && !d.is_whitespace()) {
+                        name.push(d);
+                    }
+                }
+                if leaf {
+                    leaves.push(name);
+                }
+                leaf = false;
+            }
+        }
+    }
+    leaves
+}
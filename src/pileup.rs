@@ -0,0 +1,298 @@
+// Non-interactive pileup of SAM records against reference coordinates, similar to samtools tview.
+use ansi_colours::rgb_from_ansi256;
+use anyhow::{Result, bail};
+use std::collections::HashMap;
+use yansi::Color::{self, *};
+use yansi::{Painted, Style};
+
+use crate::ansi_colors::{Char, Highlight, supported};
+use crate::bio::Region;
+use crate::render::Line;
+
+enum Cell {
+    Base(char, u8),
+    Deletion,
+    // Reference skip, e.g. an intron.
+    Skip,
+}
+
+struct Read {
+    rname: String,
+    reverse: bool,
+    // 0-based reference position of the first aligned base.
+    start: usize,
+    // Exclusive end on the reference.
+    end: usize,
+    // A cell for each reference position from start to end.
+    cells: Vec<Cell>,
+    // Inserted bases keyed by the reference position they come before.
+    insertions: HashMap<usize, Vec<(char, u8)>>,
+}
+
+fn parse_cigar(cigar: &str) -> Result<Vec<(usize, char)>> {
+    let mut ops = vec![];
+    let mut n = String::new();
+    for c in cigar.chars() {
+        if c.is_ascii_digit() {
+            n.push(c);
+        } else {
+            ops.push((n.parse()?, c));
+            n.clear();
+        }
+    }
+    Ok(ops)
+}
+
+// Parse the SAM alignment on line number n. Header lines, unmapped reads, and reads without CIGAR
+// give None.
+fn parse_read(n: usize, line: &str) -> Result<Option<Read>> {
+    if line.starts_with('@') || line.is_empty() {
+        return Ok(None);
+    }
+    let fields: Vec<&str> = line.split('\t').collect();
+    if fields.len() < 11 {
+        bail!("SAM line {n} needs at least 11 columns: {line}");
+    }
+    let flag: u16 = fields[1].parse()?;
+    if flag & 4 != 0 || fields[5] == "*" || fields[9] == "*" {
+        return Ok(None);
+    }
+    let start = fields[3].parse::<usize>()?.saturating_sub(1);
+    let seq: Vec<char> = fields[9].chars().collect();
+    let qual: Vec<u8> = match fields[10] {
+        "*" => vec![u8::MAX; seq.len()],
+        qual => qual.bytes().map(|q| q.saturating_sub(33)).collect(),
+    };
+    if qual.len() < seq.len() {
+        bail!("SAM line {n}: QUAL is shorter than SEQ: {line}");
+    }
+
+    let mut read = Read {
+        rname: fields[2].to_string(),
+        reverse: flag & 16 != 0,
+        start,
+        end: start,
+        cells: vec![],
+        insertions: HashMap::new(),
+    };
+    let mut i = 0;
+    for (n, op) in parse_cigar(fields[5])? {
+        match op {
+            'M' | '=' | 'X' => {
+                for _ in 0..n {
+                    let (&base, &q) = seq.get(i).zip(qual.get(i)).unwrap_or((&'N', &0));
+                    read.cells.push(Cell::Base(base, q));
+                    i += 1;
+                }
+            }
+            'I' => {
+                let inserted = (i..(i + n).min(seq.len())).map(|k| (seq[k], qual[k])).collect();
+                read.insertions.insert(start + read.cells.len(), inserted);
+                i += n;
+            }
+            'D' => read.cells.extend((0..n).map(|_| Cell::Deletion)),
+            'N' => read.cells.extend((0..n).map(|_| Cell::Skip)),
+            'S' => i += n,
+            _ => {} // H and P don't consume anything.
+        }
+    }
+    read.end = start + read.cells.len();
+    Ok(Some(read))
+}
+
+pub struct PileupOpts {
+    // How to show bases matching the reference. None to show "." and "," as samtools tview.
+    pub matches: Option<Highlight>,
+    // Scale brightness of bases by their quality.
+    pub quality: bool,
+}
+
+// Darken a color by a factor between 0 and 1.
+fn darken(col: Color, factor: f32) -> Color {
+    let (r, g, b) = match col {
+        Rgb(r, g, b) => (r, g, b),
+        Fixed(idx) => rgb_from_ansi256(idx),
+        col => return col,
+    };
+    let scale = |c: u8| (c as f32 * factor) as u8;
+    supported(Rgb(scale(r), scale(g), scale(b)))
+}
+
+impl PileupOpts {
+    fn base(&self, styles: &HashMap<char, Style>, base: char, q: u8) -> Char {
+        let mut style = styles.get(&base.to_ascii_uppercase()).copied().unwrap_or_default();
+        if self.quality && q != u8::MAX {
+            // Phred 40 and above is full brightness.
+            let factor = (q.min(40) as f32 / 40.).max(0.25);
+            style.foreground = style.foreground.map(|col| darken(col, factor));
+            style.background = style.background.map(|col| darken(col, factor));
+        }
+        Char::Styled(Painted { value: base, style })
+    }
+}
+
+// Lay out reads overlapping each region against the reference, with columns added for insertions.
+// Returns rows of a header, a ruler, the reference if given, and reads packed into rows.
+pub fn pileup(
+    lines: impl Iterator<Item = String>,
+    reference: &HashMap<String, Vec<char>>,
+    regions: &[Region],
+    styles: &HashMap<char, Style>,
+    opts: &PileupOpts,
+) -> Result<Vec<Line>> {
+    let mut reads = vec![];
+    for (i, line) in lines.enumerate() {
+        // Reads without reference cells, e.g. only soft clipped, have nothing to show.
+        if let Some(read) = parse_read(i + 1, &line)?
+            && read.end > read.start
+        {
+            reads.push(read);
+        }
+    }
+    // Default to the extent of the reads on each reference name in order of appearance.
+    let regions: Vec<Region> = if regions.is_empty() {
+        let mut extents: Vec<Region> = vec![];
+        for read in &reads {
+            match extents.iter_mut().find(|r| r.name == read.rname) {
+                Some(region) => {
+                    region.start = region.start.min(read.start);
+                    region.end = region.end.max(Some(read.end));
+                }
+                None => extents.push(Region {
+                    name: read.rname.clone(),
+                    start: read.start,
+                    end: Some(read.end),
+                }),
+            }
+        }
+        extents
+    } else {
+        regions.to_vec()
+    };
+
+    let mut rows = vec![];
+    for region in &regions {
+        let overlapping: Vec<&Read> = reads
+            .iter()
+            .filter(|r| r.rname == region.name && r.end > region.start)
+            .filter(|r| region.end.is_none_or(|end| r.start < end))
+            .collect();
+        let end = region.end.unwrap_or_else(|| {
+            let read_end = overlapping.iter().map(|r| r.end).max().unwrap_or(region.start);
+            read_end.max(reference.get(&region.name).map_or(0, |s| s.len()))
+        });
+        if end <= region.start {
+            continue;
+        }
+        let n = end - region.start;
+
+        // Width of insertions before each reference position and column of each position.
+        let mut widths = vec![0; n];
+        for read in &overlapping {
+            for (&p, inserted) in &read.insertions {
+                if region.contains(p) && p < end && p > region.start {
+                    widths[p - region.start] = widths[p - region.start].max(inserted.len());
+                }
+            }
+        }
+        let mut columns = Vec::with_capacity(n);
+        let mut width = 0;
+        for w in &widths {
+            width += w;
+            columns.push(width);
+            width += 1;
+        }
+        let blank = || (0..width).map(|_| Char::Unstyled(' ')).collect::<Vec<_>>();
+
+        let header = format!(">{}:{}-{}", region.name, region.start + 1, end);
+        rows.push(Line::new(header.chars().map(Char::Unstyled).collect()));
+
+        // Ruler with positions every 10 bases.
+        let mut ruler = blank();
+        let mut free = 0;
+        for p in region.start..end {
+            if (p + 1) % 10 == 0 {
+                let col = columns[p - region.start];
+                let label = (p + 1).to_string();
+                if col >= free && col + label.len() <= width {
+                    for (k, c) in label.chars().enumerate() {
+                        ruler[col + k] = Char::Unstyled(c);
+                    }
+                    free = col + label.len() + 1;
+                }
+            }
+        }
+        rows.push(Line::new(ruler));
+
+        let ref_seq = reference.get(&region.name);
+        let ref_base = |p: usize| ref_seq.and_then(|s| s.get(p)).copied();
+        if ref_seq.is_some() {
+            let mut row = blank();
+            for p in region.start..end {
+                let col = columns[p - region.start];
+                for ch in &mut row[col - widths[p - region.start]..col] {
+                    *ch = Char::Unstyled('*');
+                }
+                if let Some(base) = ref_base(p) {
+                    row[col] = opts.base(styles, base, u8::MAX);
+                }
+            }
+            rows.push(Line::new(row));
+        }
+
+        // Pack reads into rows such that reads in a row are separated by at least one column.
+        let mut packed: Vec<(usize, Vec<Char>)> = vec![];
+        for read in overlapping {
+            let first = read.start.max(region.start);
+            let last = read.end.min(end) - 1;
+            if last < first {
+                continue;
+            }
+            let first_col = columns[first - region.start] - widths[first - region.start];
+            let last_col = columns[last - region.start];
+            let row = match packed.iter_mut().find(|(free, _)| *free <= first_col) {
+                Some(row) => row,
+                None => {
+                    packed.push((0, blank()));
+                    packed.last_mut().unwrap()
+                }
+            };
+            row.0 = last_col + 2;
+            let chars = &mut row.1;
+            for p in first..=last {
+                let col = columns[p - region.start];
+                let w = widths[p - region.start];
+                // Pad insertion columns of other reads.
+                let inserted = read.insertions.get(&p).filter(|_| p > read.start);
+                if p > first || inserted.is_some() {
+                    for k in 0..w {
+                        chars[col - w + k] = match inserted.and_then(|ins| ins.get(k)) {
+                            Some(&(base, q)) => opts.base(styles, base, q),
+                            None => Char::Unstyled('*'),
+                        };
+                    }
+                }
+                chars[col] = match read.cells[p - read.start] {
+                    Cell::Deletion => Char::Unstyled('*'),
+                    Cell::Skip => Char::Unstyled(' '),
+                    Cell::Base(base, q) => {
+                        let is_match = ref_base(p).is_some_and(|r| r.eq_ignore_ascii_case(&base));
+                        match (is_match, opts.matches) {
+                            (false, _) => opts.base(styles, base, q),
+                            (true, None) => Char::Unstyled(if read.reverse { ',' } else { '.' }),
+                            (true, Some(hl)) => match opts.base(styles, base, q) {
+                                Char::Styled(mut painted) => {
+                                    painted.style = hl.apply(painted.style);
+                                    Char::Styled(painted)
+                                }
+                                ch => ch,
+                            },
+                        }
+                    }
+                };
+            }
+        }
+        rows.extend(packed.into_iter().map(|(_, chars)| Line::new(chars)));
+    }
+    Ok(rows)
+}
@@ -0,0 +1,179 @@
+use std::collections::HashSet;
+
+use crate::ansi_colors::Highlight;
+use crate::bio::{is_gap, is_nucleotide};
+use crate::render::{Line, restyle, sequence};
+
+// Sequence problems to highlight.
+#[derive(Clone, Copy, PartialEq)]
+pub enum Check {
+    // Chars not in the alphabet given by -a/--alphabet.
+    Alphabet,
+    // Stop codon "*" before the end of a sequence.
+    Stop,
+    // N and other ambiguity codes in nucleotides, X, B, Z, and J in amino acids.
+    Ambiguous,
+    Homopolymer,
+    // Gaps of a length that is not a multiple of 3 within nucleotide sequences.
+    Frameshift,
+}
+
+impl Check {
+    pub const ALL: [Check; 5] = [
+        Check::Alphabet,
+        Check::Stop,
+        Check::Ambiguous,
+        Check::Homopolymer,
+        Check::Frameshift,
+    ];
+
+    // "all" only includes the alphabet check if there is an alphabet to check against.
+    pub fn parse_list(s: &str, alphabet: bool) -> Result<Vec<Self>, String> {
+        if s == "all" {
+            return Ok(Check::ALL.into_iter().filter(|&c| alphabet || c != Check::Alphabet).collect());
+        }
+        s.split(',')
+            .map(|s| match s.trim().to_lowercase().as_str() {
+                "alphabet" => Ok(Check::Alphabet),
+                "stop" => Ok(Check::Stop),
+                "ambiguous" => Ok(Check::Ambiguous),
+                "homopolymer" => Ok(Check::Homopolymer),
+                "frameshift" => Ok(Check::Frameshift),
+                _ => Err(format!(
+                    "QC check not understood: '{s}'. \
+                    Use \"alphabet\", \"stop\", \"ambiguous\", \"homopolymer\", \"frameshift\", or \"all\"."
+                )),
+            })
+            .collect()
+    }
+
+    // Description of n findings, which are chars, or runs for homopolymers and frameshift gaps.
+    fn description(self, n: usize) -> String {
+        let plural = if n == 1 { "" } else { "s" };
+        match self {
+            Check::Alphabet => format!("{n} outside alphabet"),
+            Check::Stop => format!("{n} internal stop{plural}"),
+            Check::Ambiguous => format!("{n} ambiguous"),
+            Check::Homopolymer => format!("{n} homopolymer{plural}"),
+            Check::Frameshift => format!("{n} frameshift gap{plural}"),
+        }
+    }
+}
+
+pub struct QcOpts {
+    pub checks: Vec<Check>,
+    pub alphabet: Option<HashSet<char>>,
+    // Minimum length of a homopolymer run to flag.
+    pub homopolymer: usize,
+    pub style: Highlight,
+}
+
+// Ranges of identical consecutive items as (start, end) with exclusive end.
+pub fn runs<T: PartialEq>(items: &[T]) -> Vec<(usize, usize)> {
+    let mut runs = vec![];
+    let mut start = 0;
+    for i in 1..=items.len() {
+        if i == items.len() || items[i] != items[start] {
+            runs.push((start, i));
+            start = i;
+        }
+    }
+    runs
+}
+
+// Homopolymer runs of at least the given length as indices into the sequence.
+// Gaps are skipped, so runs may be interrupted by gaps in an alignment.
+pub fn homopolymers(seq: &[char], min_length: usize) -> Vec<Vec<usize>> {
+    let bases: Vec<usize> = (0..seq.len()).filter(|&i| !is_gap(seq[i])).collect();
+    let upper: Vec<char> = bases.iter().map(|&i| seq[i].to_ascii_uppercase()).collect();
+    runs(&upper)
+        .into_iter()
+        .filter(|&(start, end)| end - start >= min_length && !"NX".contains(upper[start]))
+        .map(|(start, end)| bases[start..end].to_vec())
+        .collect()
+}
+
+fn is_ambiguous(c: char, nucleotide: bool) -> bool {
+    let c = c.to_ascii_uppercase();
+    if nucleotide {
+        !is_gap(c) && !"ACGTU".contains(c)
+    } else {
+        "XBZJ".contains(c)
+    }
+}
+
+// Flag problems in the sequence of a record, which may be wrapped over multiple lines.
+// Returns the number of findings for each check that found any, see Check::description.
+pub fn qc(seq: &mut [Line], opts: &QcOpts) -> Vec<(Check, usize)> {
+    let (positions, chars) = sequence(seq);
+    let nucleotide = is_nucleotide(&chars);
+
+    let mut flagged = vec![false; chars.len()];
+    let mut summary = vec![];
+    for &check in &opts.checks {
+        let mut hits: Vec<usize> = vec![];
+        // Number of runs for checks flagging runs of chars.
+        let mut n_runs = 0;
+        match check {
+            Check::Alphabet => {
+                if let Some(alphabet) = &opts.alphabet {
+                    hits.extend((0..chars.len()).filter(|&k| {
+                        !alphabet.contains(&chars[k])
+                            && !alphabet.contains(&chars[k].to_ascii_uppercase())
+                    }));
+                }
+            }
+            Check::Stop => {
+                // The last residue is allowed to be a stop.
+                let last = chars.iter().rposition(|&c| !is_gap(c));
+                hits.extend((0..chars.len()).filter(|&k| chars[k] == '*' && Some(k) != last));
+            }
+            Check::Ambiguous => {
+                hits.extend((0..chars.len()).filter(|&k| is_ambiguous(chars[k], nucleotide)));
+            }
+            Check::Homopolymer => {
+                let runs = homopolymers(&chars, opts.homopolymer);
+                n_runs = runs.len();
+                hits.extend(runs.into_iter().flatten());
+            }
+            Check::Frameshift => {
+                if nucleotide {
+                    let gaps: Vec<bool> = chars.iter().map(|&c| is_gap(c)).collect();
+                    for (start, end) in runs(&gaps) {
+                        // Only internal gaps, since terminal gaps are just the ends of an alignment.
+                        let internal = start > 0 && end < chars.len();
+                        if gaps[start] && internal && (end - start) % 3 != 0 {
+                            hits.extend(start..end);
+                            n_runs += 1;
+                        }
+                    }
+                }
+            }
+        }
+        if !hits.is_empty() {
+            let n = match check {
+                Check::Homopolymer | Check::Frameshift => n_runs,
+                _ => hits.len(),
+            };
+            summary.push((check, n));
+        }
+        for k in hits {
+            flagged[k] = true;
+        }
+    }
+
+    for (k, &pos) in positions.iter().enumerate() {
+        if flagged[k] {
+            restyle(seq, pos, |style| opts.style.apply(style));
+        }
+    }
+    summary
+}
+
+pub fn format_summary(summary: &[(Check, usize)]) -> String {
+    summary
+        .iter()
+        .map(|(check, n)| check.description(*n))
+        .collect::<Vec<_>>()
+        .join(", ")
+}
@@ -0,0 +1,129 @@
+// Grouping of input lines into fasta/fastq/GenBank/EMBL records, so that features working on a
+// whole sequence (e.g. translation) can see sequences wrapped over multiple lines.
+// Lines outside any record (e.g. plain text or one sequence per line) each become their own
+// record without a header, so flattening records always gives back the input lines in order.
+
+pub struct Record<T> {
+    // Fasta ">", fastq "@", GenBank "LOCUS", or EMBL "ID" line.
+    pub header: Option<T>,
+    // GenBank/EMBL annotation lines up to and including "ORIGIN" or "SQ".
+    pub meta: Vec<T>,
+    pub seq: Vec<T>,
+    // Fastq "+" line.
+    pub sep: Option<T>,
+    // Fastq quality lines.
+    pub qual: Vec<T>,
+    // GenBank/EMBL "//" line.
+    pub end: Option<T>,
+    // Whether sequence lines are numbered blocks as in GenBank/EMBL, where only letters are
+    // part of the sequence.
+    pub blocks: bool,
+}
+
+impl<T> Record<T> {
+    fn new(header: Option<T>) -> Self {
+        Record { header, meta: vec![], seq: vec![], sep: None, qual: vec![], end: None, blocks: false }
+    }
+
+    pub fn map<U>(self, mut f: impl FnMut(T) -> U) -> Record<U> {
+        Record {
+            header: self.header.map(&mut f),
+            meta: self.meta.into_iter().map(&mut f).collect(),
+            seq: self.seq.into_iter().map(&mut f).collect(),
+            sep: self.sep.map(&mut f),
+            qual: self.qual.into_iter().map(&mut f).collect(),
+            end: self.end.map(&mut f),
+            blocks: self.blocks,
+        }
+    }
+
+    // All lines in their original order.
+    pub fn into_lines(self) -> impl Iterator<Item = T> {
+        self.header
+            .into_iter()
+            .chain(self.meta)
+            .chain(self.seq)
+            .chain(self.sep)
+            .chain(self.qual)
+            .chain(self.end)
+    }
+}
+
+impl Record<String> {
+    // Header without the leading ">" or "@" and any description, or the GenBank/EMBL name.
+    pub fn id(&self) -> Option<&str> {
+        let header = self.header.as_ref()?;
+        if self.blocks {
+            return header.split_whitespace().nth(1).map(|id| id.trim_end_matches(';'));
+        }
+        header[1..].split_whitespace().next()
+    }
+}
+
+// GenBank LOCUS or EMBL ID line.
+pub fn is_flat_file_header(line: &str) -> bool {
+    line.starts_with("LOCUS   ") || line.starts_with("ID   ")
+}
+
+pub struct Records<I: Iterator<Item = String>> {
+    lines: std::iter::Peekable<I>,
+}
+
+pub fn records<I: Iterator<Item = String>>(lines: I) -> Records<I> {
+    Records { lines: lines.peekable() }
+}
+
+impl<I: Iterator<Item = String>> Iterator for Records<I> {
+    type Item = Record<String>;
+
+    fn next(&mut self) -> Option<Record<String>> {
+        let first = self.lines.next()?;
+        if is_flat_file_header(&first) {
+            let mut record = Record::new(Some(first));
+            record.blocks = true;
+            for line in self.lines.by_ref() {
+                let last = line.starts_with("ORIGIN") || line.starts_with("SQ   ");
+                record.meta.push(line);
+                if last {
+                    break;
+                }
+            }
+            while let Some(line) = self.lines.next_if(|l| !l.starts_with("//")) {
+                record.seq.push(line);
+            }
+            record.end = self.lines.next();
+            Some(record)
+        } else if first.starts_with('>') {
+            let mut record = Record::new(Some(first));
+            while let Some(line) = self.lines.next_if(|l| !l.starts_with('>')) {
+                record.seq.push(line);
+            }
+            Some(record)
+        } else if first.starts_with('@') {
+            let mut record = Record::new(Some(first));
+            while let Some(line) = self.lines.next_if(|l| !l.starts_with('+') && !l.starts_with('@')) {
+                record.seq.push(line);
+            }
+            if let Some(sep) = self.lines.next_if(|l| l.starts_with('+')) {
+                record.sep = Some(sep);
+                // Quality lines may start with "@" so we count their length instead.
+                let seq_len: usize = record.seq.iter().map(|l| l.len()).sum();
+                let mut qual_len = 0;
+                while qual_len < seq_len {
+                    match self.lines.next() {
+                        None => break,
+                        Some(line) => {
+                            qual_len += line.len();
+                            record.qual.push(line);
+                        }
+                    }
+                }
+            }
+            Some(record)
+        } else {
+            let mut record = Record::new(None);
+            record.seq.push(first);
+            Some(record)
+        }
+    }
+}
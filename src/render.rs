@@ -0,0 +1,169 @@
+use regex::Regex;
+use std::collections::HashMap;
+use std::io::{self, Write};
+use yansi::Style;
+
+use crate::ansi_colors::{Char, Segment, ansi_byte, segments, to_painted};
+
+// A painted line of input along with extra rows to show beneath it, e.g. a translation.
+// Tracks are not part of the sequence, so they are ignored by e.g. consensus.
+pub struct Line {
+    pub chars: Vec<Char>,
+    pub tracks: Vec<Vec<Char>>,
+}
+
+impl Line {
+    pub fn new(chars: Vec<Char>) -> Self {
+        Line { chars, tracks: vec![] }
+    }
+
+    // Number of rows this line will take up when written.
+    pub fn n_rows(&self) -> usize {
+        1 + self.tracks.len()
+    }
+
+    pub fn row(&self, i: usize) -> &[Char] {
+        match i {
+            0 => &self.chars,
+            _ => &self.tracks[i - 1],
+        }
+    }
+
+    pub fn write(&self, buf: &mut (impl Write + ?Sized)) -> io::Result<()> {
+        for i in 0..self.n_rows() {
+            for ch in self.row(i) {
+                ch.write(buf)?;
+            }
+            buf.write_all(&ansi_byte('\n'))?;
+        }
+        Ok(())
+    }
+}
+
+// The styled chars of lines, i.e. a sequence that may be wrapped over multiple lines, along with
+// their positions as (line index, char index).
+pub fn sequence(lines: &[Line]) -> (Vec<(usize, usize)>, Vec<char>) {
+    let mut positions = vec![];
+    let mut chars = vec![];
+    for (i, line) in lines.iter().enumerate() {
+        for (j, ch) in line.chars.iter().enumerate() {
+            if let Char::Styled(painted) = ch {
+                positions.push((i, j));
+                chars.push(painted.value);
+            }
+        }
+    }
+    (positions, chars)
+}
+
+// Change the style of a char in a sequence given its position from `sequence`.
+pub fn restyle(lines: &mut [Line], (i, j): (usize, usize), f: impl FnOnce(Style) -> Style) {
+    if let Char::Styled(painted) = &mut lines[i].chars[j] {
+        painted.style = f(painted.style);
+    }
+}
+
+// Paint the chars of a line matching all the regexes, where the second regex is applied within
+// matches of the first. Lowercase chars are removed from matches if hiding lowercase.
+pub fn paint(
+    styles: &HashMap<char, Style>,
+    regexes: &[Regex],
+    hide_lowercase: bool,
+    line: &str,
+) -> Vec<Char> {
+    if line.contains('\x1B') {
+        return paint_escaped(styles, regexes, hide_lowercase, line);
+    }
+    let mut line_painted: Vec<Char> = Vec::with_capacity(line.len());
+    match regexes {
+        [] => line_painted.extend(to_painted(styles, hide_lowercase, line)),
+        [re] => {
+            let mut i = 0;
+            for m in re.find_iter(line) {
+                line_painted.extend(line[i..m.start()].chars().map(Char::Unstyled));
+                line_painted.extend(to_painted(styles, hide_lowercase, m.as_str()));
+                i = m.end();
+            }
+            line_painted.extend(line[i..].chars().map(Char::Unstyled));
+        }
+        [re0, re1] => {
+            // Boolean logic: color only if both regex filters says yes.
+            let mut i = 0;
+            for m0 in re0.find_iter(line) {
+                line_painted.extend(line[i..m0.start()].chars().map(Char::Unstyled));
+                i = m0.start();
+                for m1 in re1.find_iter(m0.as_str()) {
+                    let start = m0.start() + m1.start();
+                    line_painted.extend(line[i..start].chars().map(Char::Unstyled));
+                    line_painted.extend(to_painted(styles, hide_lowercase, m1.as_str()));
+                    i = m0.start() + m1.end();
+                }
+                line_painted.extend(line[i..m0.end()].chars().map(Char::Unstyled));
+                i = m0.end();
+            }
+            line_painted.extend(line[i..].chars().map(Char::Unstyled));
+        }
+        _ => unimplemented!(), // Unreachable
+    }
+    line_painted
+}
+
+// Paint a line with ANSI escape sequences kept as is. Only the visible text is matched by the
+// regexes, and text already styled by the escape sequences is not colored.
+fn paint_escaped(
+    styles: &HashMap<char, Style>,
+    regexes: &[Regex],
+    hide_lowercase: bool,
+    line: &str,
+) -> Vec<Char> {
+    let segments = segments(line);
+    let visible: String = segments
+        .iter()
+        .filter_map(|segment| match segment {
+            Segment::Text(text, _) => Some(*text),
+            Segment::Escape(_) => None,
+        })
+        .collect();
+    // Lowercase chars are hidden afterwards to keep the painted chars aligned with the text.
+    let mut painted = paint(styles, regexes, false, &visible).into_iter();
+    let mut chars = vec![];
+    for segment in segments {
+        match segment {
+            Segment::Escape(escape) => chars.extend(escape.chars().map(Char::Unstyled)),
+            Segment::Text(text, styled) => {
+                for ch in painted.by_ref().take(text.chars().count()) {
+                    match ch {
+                        Char::Styled(p) if styled => chars.push(Char::Unstyled(p.value)),
+                        Char::Styled(p) if hide_lowercase && p.value.is_lowercase() => {}
+                        ch => chars.push(ch),
+                    }
+                }
+            }
+        }
+    }
+    chars
+}
+
+// Write lines with columns and rows swapped. Tracks become extra columns after their line.
+pub fn write_transposed(buf: &mut (impl Write + ?Sized), lines: &[Line]) -> io::Result<()> {
+    let space = ansi_byte(' ');
+    let max_line = lines
+        .iter()
+        .flat_map(|line| (0..line.n_rows()).map(|i| line.row(i).len()))
+        .max()
+        .unwrap_or(0);
+    for j in 0..max_line {
+        for line in lines {
+            for i in 0..line.n_rows() {
+                match line.row(i).get(j) {
+                    None => buf.write_all(&space)?,
+                    Some(ch) => {
+                        ch.write(buf)?;
+                    }
+                };
+            }
+        }
+        buf.write_all(&ansi_byte('\n'))?;
+    }
+    Ok(())
+}
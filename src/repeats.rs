@@ -0,0 +1,184 @@
+use crate::ansi_colors::Highlight;
+use crate::bio::{is_gap, is_nucleotide};
+use crate::qc::homopolymers;
+use crate::render::{Line, restyle, sequence};
+
+// Kinds of repetitive sequence to highlight.
+#[derive(Clone, Copy, PartialEq)]
+pub enum Repeat {
+    Homopolymer,
+    // DUST for nucleotides and SEG for amino acids.
+    LowComplexity,
+    // Short tandem repeats with a period of 2 to 6.
+    Tandem,
+}
+
+impl Repeat {
+    pub const ALL: [Repeat; 3] = [Repeat::Homopolymer, Repeat::LowComplexity, Repeat::Tandem];
+
+    pub fn parse_list(s: &str) -> Result<Vec<Self>, String> {
+        if s == "all" {
+            return Ok(Repeat::ALL.to_vec());
+        }
+        s.split(',')
+            .map(|s| match s.trim().to_lowercase().as_str() {
+                "homopolymer" => Ok(Repeat::Homopolymer),
+                "lowcomplexity" | "dust" | "seg" => Ok(Repeat::LowComplexity),
+                "tandem" => Ok(Repeat::Tandem),
+                _ => Err(format!(
+                    "Repeat kind not understood: '{s}'. \
+                    Use \"homopolymer\", \"lowcomplexity\", \"tandem\", or \"all\"."
+                )),
+            })
+            .collect()
+    }
+}
+
+pub struct RepeatOpts {
+    pub kinds: Vec<Repeat>,
+    // Minimum length of homopolymer runs.
+    pub homopolymer: usize,
+    // Minimum total length of tandem repeats.
+    pub tandem: usize,
+    pub style: Highlight,
+}
+
+// DUST parameters as used by default in dustmasker.
+const DUST_WINDOW: usize = 64;
+const DUST_THRESHOLD: f64 = 2.0;
+// SEG parameters for the trigger window.
+const SEG_WINDOW: usize = 12;
+const SEG_ENTROPY: f64 = 2.2;
+
+// Mark windows of nucleotides with overrepresented triplets.
+// The score of a window is sum c(c-1)/2 / (l-1) over counts c of each of the l triplets.
+fn dust(seq: &[char], mask: &mut [bool]) {
+    if seq.len() < DUST_WINDOW {
+        return;
+    }
+    let triplets: Vec<Option<usize>> = seq
+        .windows(3)
+        .map(|w| {
+            let mut idx = 0;
+            for c in w {
+                idx = idx * 4 + "ACGT".find(c.to_ascii_uppercase())?;
+            }
+            Some(idx)
+        })
+        .collect();
+    let l = DUST_WINDOW - 2;
+    let mut counts = [0usize; 64];
+    // Sum of c(c-1)/2 kept up to date as triplets enter and leave the window.
+    let mut sum = 0;
+    for (i, t) in triplets.iter().enumerate() {
+        if let Some(t) = *t {
+            sum += counts[t];
+            counts[t] += 1;
+        }
+        if i >= l
+            && let Some(t) = triplets[i - l]
+        {
+            counts[t] -= 1;
+            sum -= counts[t];
+        }
+        if i + 1 >= l && sum as f64 / (l - 1) as f64 > DUST_THRESHOLD {
+            let start = i + 1 - l;
+            mask[start..start + DUST_WINDOW].fill(true);
+        }
+    }
+}
+
+// Mark windows of amino acids with low Shannon entropy.
+fn seg(seq: &[char], mask: &mut [bool]) {
+    if seq.len() < SEG_WINDOW {
+        return;
+    }
+    let mut counts = [0usize; 26];
+    let index = |c: char| {
+        let c = c.to_ascii_uppercase();
+        c.is_ascii_uppercase().then(|| (c as u8 - b'A') as usize)
+    };
+    for (i, &c) in seq.iter().enumerate() {
+        if let Some(k) = index(c) {
+            counts[k] += 1;
+        }
+        if i >= SEG_WINDOW
+            && let Some(k) = index(seq[i - SEG_WINDOW])
+        {
+            counts[k] -= 1;
+        }
+        if i + 1 >= SEG_WINDOW {
+            let n = SEG_WINDOW as f64;
+            let entropy: f64 = counts
+                .iter()
+                .filter(|&&c| c > 0)
+                .map(|&c| {
+                    let p = c as f64 / n;
+                    -p * p.log2()
+                })
+                .sum();
+            if entropy < SEG_ENTROPY {
+                let start = i + 1 - SEG_WINDOW;
+                mask[start..=i].fill(true);
+            }
+        }
+    }
+}
+
+// Mark stretches where the sequence repeats itself with a period of 2 to 6, at least 3 times and
+// with a total length of at least min_length.
+fn tandem(seq: &[char], min_length: usize, mask: &mut [bool]) {
+    for period in 2..=6 {
+        let mut start = 0;
+        for i in 0..=seq.len().saturating_sub(period) {
+            let matches = i + period < seq.len()
+                && seq[i].eq_ignore_ascii_case(&seq[i + period]);
+            if !matches {
+                // Stretch from start to i + period repeats with the period.
+                let end = i + period;
+                let length = end - start;
+                if length >= min_length.max(3 * period) {
+                    mask[start..end.min(seq.len())].fill(true);
+                }
+                start = i + 1;
+            }
+        }
+    }
+}
+
+// Highlight repetitive parts of the sequence of a record, which may be wrapped over multiple
+// lines. Gaps are skipped.
+pub fn paint_repeats(seq: &mut [Line], opts: &RepeatOpts) {
+    let (positions, chars) = sequence(seq);
+    let bases: Vec<usize> = (0..chars.len()).filter(|&k| !is_gap(chars[k])).collect();
+    let ungapped: Vec<char> = bases.iter().map(|&k| chars[k]).collect();
+
+    let mut mask = vec![false; ungapped.len()];
+    let mut flagged = vec![false; chars.len()];
+    for &kind in &opts.kinds {
+        match kind {
+            Repeat::Homopolymer => {
+                for k in homopolymers(&chars, opts.homopolymer).into_iter().flatten() {
+                    flagged[k] = true;
+                }
+            }
+            Repeat::LowComplexity => {
+                if is_nucleotide(&ungapped) {
+                    dust(&ungapped, &mut mask);
+                } else {
+                    seg(&ungapped, &mut mask);
+                }
+            }
+            Repeat::Tandem => tandem(&ungapped, opts.tandem, &mut mask),
+        }
+    }
+    for (b, &k) in bases.iter().enumerate() {
+        flagged[k] |= mask[b];
+    }
+
+    for (k, &pos) in positions.iter().enumerate() {
+        if flagged[k] {
+            restyle(seq, pos, |style| opts.style.apply(style));
+        }
+    }
+}
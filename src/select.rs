@@ -0,0 +1,63 @@
+// Selection of records by ID, header pattern, or sequence motif.
+use anyhow::Result;
+use regex::{Regex, RegexBuilder};
+use std::collections::HashSet;
+use std::fs;
+
+use crate::records::Record;
+
+// Filters that must all match for a record to be selected.
+pub struct Selection {
+    ids: Option<HashSet<String>>,
+    header: Option<Regex>,
+    motif: Option<Regex>,
+}
+
+impl Selection {
+    // IDs are given directly and/or one per line in a file. None if there are no filters.
+    pub fn new(ids: &[String], id_file: Option<&str>, header: Option<&str>, motif: Option<&str>) -> Result<Option<Self>> {
+        let mut id_set: Option<HashSet<String>> = None;
+        if !ids.is_empty() || id_file.is_some() {
+            let mut set: HashSet<String> = ids.iter().map(|id| id.trim().to_string()).collect();
+            if let Some(path) = id_file {
+                let text = fs::read_to_string(path).map_err(|e| anyhow::anyhow!("{path}: {e}"))?;
+                // The first word of each line, so a list of headers also works.
+                set.extend(text.lines().filter_map(|line| line.trim_start_matches(['>', '@']).split_whitespace().next()).map(String::from));
+            }
+            id_set = Some(set);
+        }
+        let header = header.map(Regex::new).transpose()?;
+        let motif = motif.map(|m| RegexBuilder::new(m).case_insensitive(true).build()).transpose()?;
+        if id_set.is_none() && header.is_none() && motif.is_none() {
+            return Ok(None);
+        }
+        Ok(Some(Selection { ids: id_set, header, motif }))
+    }
+
+    pub fn matches(&self, record: &Record<String>) -> bool {
+        if let Some(ids) = &self.ids
+            && !record.id().is_some_and(|id| ids.contains(id))
+        {
+            return false;
+        }
+        if let Some(re) = &self.header
+            && !record.header.as_ref().is_some_and(|header| re.is_match(header))
+        {
+            return false;
+        }
+        if let Some(re) = &self.motif {
+            // Search the sequence as a whole, i.e. unwrapped and without gaps, and for
+            // GenBank/EMBL only the letters.
+            let seq: String = record
+                .seq
+                .iter()
+                .flat_map(|line| line.chars())
+                .filter(|&c| if record.blocks { c.is_alphabetic() } else { !c.is_whitespace() && c != '-' && c != '.' })
+                .collect();
+            if !re.is_match(&seq) {
+                return false;
+            }
+        }
+        true
+    }
+}
@@ -0,0 +1,137 @@
+// Ordering of records, e.g. to read an alignment by similarity or by a tree.
+use anyhow::{Result, bail};
+use std::collections::HashMap;
+use std::fs;
+use yansi::{Painted, Style};
+
+use crate::ansi_colors::Char;
+use crate::bio::is_gap;
+use crate::consensus::{self, ConsensusOpts, Counts};
+use crate::phylo::read_newick_leaves;
+use crate::records::Record;
+use crate::render::Line;
+
+pub enum SortKey {
+    Name,
+    // Ungapped length, longest first.
+    Length,
+    // Fraction of gaps, fewest first.
+    Gaps,
+    // Identity to the record with the given ID or the first record, most similar first.
+    Identity(Option<String>),
+    // Identity to the consensus, most similar first.
+    Consensus,
+    // Leaf order of a Newick tree.
+    Tree(Vec<String>),
+}
+
+impl SortKey {
+    pub fn parse(s: &str) -> Result<Self> {
+        let (key, arg) = match s.split_once(':') {
+            Some((key, arg)) => (key, Some(arg)),
+            None => (s, None),
+        };
+        match (key.to_lowercase().as_str(), arg) {
+            ("name", None) => Ok(SortKey::Name),
+            ("length", None) => Ok(SortKey::Length),
+            ("gaps", None) => Ok(SortKey::Gaps),
+            ("identity", id) => Ok(SortKey::Identity(id.map(String::from))),
+            ("consensus", None) => Ok(SortKey::Consensus),
+            ("tree", Some(path)) => {
+                let text = fs::read_to_string(path).map_err(|e| anyhow::anyhow!("{path}: {e}"))?;
+                Ok(SortKey::Tree(read_newick_leaves(&text)))
+            }
+            _ => bail!(
+                "Sort key not understood: '{s}'. Use \"name\", \"length\", \"gaps\", \"identity\", \"identity:ID\", \"consensus\", or \"tree:FILE\"."
+            ),
+        }
+    }
+}
+
+// The sequence of a record unwrapped, and for GenBank/EMBL only the letters.
+fn sequence(record: &Record<String>) -> Vec<char> {
+    record
+        .seq
+        .iter()
+        .flat_map(|line| line.chars())
+        .filter(|&c| if record.blocks { c.is_alphabetic() } else { !c.is_whitespace() })
+        .collect()
+}
+
+// Fraction of identical columns out of the columns where neither sequence has a gap, ignoring case.
+fn identity(seq: &[char], other: &[char]) -> f64 {
+    let mut same = 0;
+    let mut total = 0;
+    for (a, b) in seq.iter().zip(other) {
+        if !is_gap(*a) && !is_gap(*b) {
+            total += 1;
+            same += a.eq_ignore_ascii_case(b) as usize;
+        }
+    }
+    if total == 0 { 0.0 } else { same as f64 / total as f64 }
+}
+
+// Identity of each sequence to the consensus as counted for -c/--consensus, so the order agrees
+// with the highlighted consensus.
+fn consensus_identities(seqs: &[Vec<char>], opts: &ConsensusOpts) -> Vec<f64> {
+    let lines: Vec<Line> = seqs
+        .iter()
+        .map(|seq| Line::new(seq.iter().map(|&c| Char::Styled(Painted { value: c, style: Style::new() })).collect()))
+        .collect();
+    let mut counts = Counts::new();
+    counts.add(&lines, &vec![1.0; lines.len()], opts, 1);
+    if opts.henikoff {
+        let weights: Vec<f64> = lines.iter().map(|line| counts.henikoff(std::slice::from_ref(line), opts)).collect();
+        counts = Counts::new();
+        counts.add(&lines, &weights, opts, 1);
+    }
+    let columns = counts.consensus(opts);
+    lines.iter().map(|line| consensus::identity(std::slice::from_ref(line), &columns, opts)).collect()
+}
+
+// Sort records stably, so ties keep their input order.
+pub fn sort(records: &mut Vec<Record<String>>, key: &SortKey, opts: &ConsensusOpts) -> Result<()> {
+    let mut order: Vec<usize> = (0..records.len()).collect();
+    if let SortKey::Name = key {
+        order.sort_by_key(|&i| records[i].id().unwrap_or_default().to_string());
+        reorder(records, order);
+        return Ok(());
+    }
+    let seqs: Vec<Vec<char>> = records.iter().map(sequence).collect();
+    // A number per record where smaller comes first.
+    let scores: Vec<f64> = match key {
+        SortKey::Name => unreachable!(),
+        SortKey::Length => seqs.iter().map(|seq| -(seq.iter().filter(|&&c| !is_gap(c)).count() as f64)).collect(),
+        SortKey::Gaps => seqs
+            .iter()
+            .map(|seq| seq.iter().filter(|&&c| is_gap(c)).count() as f64 / seq.len().max(1) as f64)
+            .collect(),
+        SortKey::Identity(id) => {
+            let reference = match id {
+                None => 0,
+                Some(id) => match records.iter().position(|record| record.id() == Some(id.as_str())) {
+                    Some(i) => i,
+                    None => bail!("Reference record for sorting not found: '{id}'."),
+                },
+            };
+            seqs.iter().map(|seq| -identity(seq, &seqs[reference])).collect()
+        }
+        SortKey::Consensus => consensus_identities(&seqs, opts).iter().map(|identity| -identity).collect(),
+        SortKey::Tree(leaves) => {
+            // Records missing from the tree go last.
+            let rank: HashMap<&str, usize> = leaves.iter().enumerate().map(|(i, leaf)| (leaf.as_str(), i)).collect();
+            records
+                .iter()
+                .map(|record| record.id().and_then(|id| rank.get(id)).map_or(f64::INFINITY, |&i| i as f64))
+                .collect()
+        }
+    };
+    order.sort_by(|&i, &j| scores[i].total_cmp(&scores[j]));
+    reorder(records, order);
+    Ok(())
+}
+
+fn reorder(records: &mut Vec<Record<String>>, order: Vec<usize>) {
+    let mut taken: Vec<Option<Record<String>>> = records.drain(..).map(Some).collect();
+    records.extend(order.into_iter().map(|i| taken[i].take().unwrap()));
+}
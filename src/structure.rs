@@ -0,0 +1,188 @@
+use ansi_colours::rgb_from_ansi256;
+use anyhow::Result;
+use std::collections::HashMap;
+use std::io::BufRead;
+use yansi::Color::{self, *};
+use yansi::{Painted, Style};
+
+use crate::ansi_colors::{Char, supported};
+use crate::bio::is_gap;
+use crate::inout::open;
+use crate::render::{Line, restyle, sequence};
+
+// Colors of the strongest helix former (E) in helix_propensity_aa, and strand former (V) in
+// strand_propensity_aa. Turns get the color of the strongest helix breakers (G, P).
+const HELIX: Color = Rgb(255, 0, 255);
+const STRAND: Color = Rgb(255, 255, 0);
+const TURN: Color = Rgb(4, 255, 0);
+
+// Color of a DSSP state. Coil and unknown states, e.g. RNA base pairs, have no color.
+fn state_color(state: char) -> Option<Color> {
+    match state {
+        'H' | 'G' | 'I' => Some(HELIX),
+        'E' | 'B' => Some(STRAND),
+        'T' => Some(TURN),
+        _ => None,
+    }
+}
+
+// Per residue structure labels, e.g. DSSP states.
+pub struct Structure {
+    pub states: Vec<char>,
+    // Whether states are given for each alignment column rather than each residue.
+    pub gapped: bool,
+}
+
+// Structures keyed by record ID, where the empty string means all records.
+pub type Structures = HashMap<String, Vec<Structure>>;
+
+// Read a file with structure labels given as "ID=FILE" or "FILE".
+// Stockholm files give "#=GC SS_cons" for the ID or all records, and "#=GR <ID> SS" per record.
+// PSIPRED .ss2 files and plain strings give structure for the ID or all records.
+// Fasta files of DSSP strings give structure per record ID.
+pub fn read_structure(arg: &str, structures: &mut Structures) -> Result<()> {
+    let (id, path) = match arg.split_once('=') {
+        Some((id, path)) if !std::path::Path::new(arg).exists() => (id, path),
+        _ => ("", arg),
+    };
+    let lines: Vec<String> = open(path).map_err(|e| anyhow::anyhow!("{path}: {e}"))?.lines().collect::<Result<_, _>>()?;
+    let first = lines.iter().find(|l| !l.trim().is_empty()).map_or("", |l| l.as_str());
+
+    let mut add = |id: &str, states: Vec<char>, gapped: bool| {
+        structures.entry(id.to_string()).or_default().push(Structure { states, gapped });
+    };
+
+    if first.starts_with("# STOCKHOLM") {
+        let mut cons = vec![];
+        let mut per_seq: Vec<(String, Vec<char>)> = vec![];
+        for line in &lines {
+            let fields: Vec<&str> = line.split_whitespace().collect();
+            match fields.as_slice() {
+                ["#=GC", "SS_cons", ss] => cons.extend(ss.chars()),
+                ["#=GR", seq_id, "SS", ss] => match per_seq.iter_mut().find(|(i, _)| i == seq_id) {
+                    Some((_, states)) => states.extend(ss.chars()),
+                    None => per_seq.push((seq_id.to_string(), ss.chars().collect())),
+                },
+                _ => {}
+            }
+        }
+        if !cons.is_empty() {
+            add(id, cons, true);
+        }
+        for (seq_id, states) in per_seq {
+            add(&seq_id, states, true);
+        }
+    } else if first.starts_with("# PSIPRED") || path.ends_with(".ss2") {
+        let states = lines
+            .iter()
+            .filter(|l| !l.starts_with('#'))
+            .filter_map(|l| l.split_whitespace().nth(2)?.chars().next())
+            .collect();
+        add(id, states, false);
+    } else if first.starts_with('>') {
+        let mut entries: Vec<(String, Vec<char>)> = vec![];
+        for line in &lines {
+            match line.strip_prefix('>') {
+                Some(header) => {
+                    let header_id = header.split_whitespace().next().unwrap_or("");
+                    entries.push((header_id.to_string(), vec![]));
+                }
+                None => {
+                    if let Some((_, states)) = entries.last_mut() {
+                        states.extend(line.chars());
+                    }
+                }
+            }
+        }
+        for (header_id, states) in entries {
+            // An explicit ID overrides the first entry's ID, e.g. from a file of a single chain.
+            match id {
+                "" => add(&header_id, states, false),
+                id => {
+                    add(id, states, false);
+                    break;
+                }
+            }
+        }
+    } else {
+        let states = lines.iter().flat_map(|l| l.trim_end().chars()).collect();
+        add(id, states, false);
+    }
+    Ok(())
+}
+
+pub struct StructureOpts {
+    pub structures: Structures,
+    // Tint residues by their structural state.
+    pub tint: bool,
+}
+
+// Mix a color halfway towards another, or replace it if not RGB or ANSI 256.
+fn tint(col: Option<Color>, towards: Color) -> Color {
+    let col = match col {
+        Some(Fixed(idx)) => {
+            let (r, g, b) = rgb_from_ansi256(idx);
+            Some(Rgb(r, g, b))
+        }
+        col => col,
+    };
+    match (col, towards) {
+        (Some(Rgb(r0, g0, b0)), Rgb(r1, g1, b1)) => {
+            let mix = |a: u8, b: u8| ((a as u16 + b as u16) / 2) as u8;
+            Rgb(mix(r0, r1), mix(g0, g1), mix(b0, b1))
+        }
+        _ => towards,
+    }
+}
+
+// Show structure rows beneath each line of sequence of a record given by its ID.
+pub fn paint_structure(seq: &mut [Line], id: &str, opts: &StructureOpts) {
+    let all = opts.structures.get("").into_iter().flatten();
+    let own = opts.structures.get(id).into_iter().flatten();
+    let structures: Vec<&Structure> = all.chain(own).collect();
+    if structures.is_empty() {
+        return;
+    }
+    let (positions, chars) = sequence(seq);
+    let residues: Vec<usize> = (0..chars.len()).filter(|&k| !is_gap(chars[k])).collect();
+
+    for (n, structure) in structures.iter().enumerate() {
+        let mut tracks: Vec<Vec<Char>> = seq
+            .iter()
+            .map(|line| (0..line.chars.len()).map(|_| Char::Unstyled(' ')).collect())
+            .collect();
+        let indices: &mut dyn Iterator<Item = usize> = if structure.gapped {
+            &mut (0..chars.len())
+        } else {
+            &mut residues.iter().copied()
+        };
+        for (k, &state) in indices.zip(&structure.states) {
+            let (i, j) = positions[k];
+            let (glyph, col) = match state {
+                // Unassigned in DSSP and gaps in Stockholm.
+                ' ' | '-' | '.' | '_' => ('C', None),
+                state => (state, state_color(state)),
+            };
+            let style = match col {
+                None => Style::new(),
+                Some(col) => Style::new().bg(supported(col)).fg(Black),
+            };
+            tracks[i][j] = Char::Styled(Painted { value: glyph, style });
+            // Only tint by the first structure if multiple are attached.
+            if opts.tint
+                && n == 0
+                && !is_gap(chars[k])
+                && let Some(col) = col
+            {
+                restyle(seq, (i, j), |style| style.bg(supported(tint(style.background, col))));
+            }
+        }
+        for (line, mut track) in seq.iter_mut().zip(tracks) {
+            // Padding only up to the last state.
+            while let Some(Char::Unstyled(' ')) = track.last() {
+                track.pop();
+            }
+            line.tracks.push(track);
+        }
+    }
+}
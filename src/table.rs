@@ -0,0 +1,140 @@
+// Coloring selected columns of CSV, TSV, and other delimited tables.
+use anyhow::{Result, bail};
+use std::ops::Range;
+
+use crate::ansi_colors::Char;
+use crate::render::Line;
+
+// Byte ranges of the fields of a delimited line. Delimiters within double quotes don't split.
+fn split_fields(line: &str, delimiter: char) -> Vec<Range<usize>> {
+    let mut fields = vec![];
+    let mut start = 0;
+    let mut quoted = false;
+    for (i, c) in line.char_indices() {
+        if c == '"' {
+            // Escaped quotes "" toggle twice.
+            quoted = !quoted;
+        } else if c == delimiter && !quoted {
+            fields.push(start..i);
+            start = i + c.len_utf8();
+        }
+    }
+    fields.push(start..line.len());
+    fields
+}
+
+// Range of the content of a field without surrounding quotes.
+fn unquote(line: &str, field: Range<usize>) -> Range<usize> {
+    let text = &line[field.clone()];
+    if text.len() >= 2 && text.starts_with('"') && text.ends_with('"') {
+        field.start + 1..field.end - 1
+    } else {
+        field
+    }
+}
+
+// Guess the delimiter as the one splitting the header into the most fields, preferring tab.
+fn detect_delimiter(header: &str) -> char {
+    ['\t', ',', ';', '|']
+        .into_iter()
+        .rev()
+        .max_by_key(|&d| split_fields(header, d).len())
+        .unwrap()
+}
+
+pub struct Table {
+    pub header: String,
+    pub delimiter: char,
+    // Indices of the columns to color.
+    pub columns: Vec<usize>,
+}
+
+impl Table {
+    // Select columns by header name or 1-based index. The delimiter is guessed if not given.
+    pub fn new(header: String, delimiter: Option<char>, specs: &[String]) -> Result<Self> {
+        let delimiter = delimiter.unwrap_or_else(|| detect_delimiter(&header));
+        let names: Vec<&str> = split_fields(&header, delimiter)
+            .into_iter()
+            .map(|field| header[unquote(&header, field)].trim())
+            .collect();
+        let mut columns = vec![];
+        for spec in specs {
+            match names.iter().position(|name| name == spec) {
+                Some(i) => columns.push(i),
+                None => match spec.parse::<usize>() {
+                    Ok(i) if i > 0 => columns.push(i - 1),
+                    _ => bail!("Column not found in table header: '{spec}'."),
+                },
+            }
+        }
+        Ok(Table { header, delimiter, columns })
+    }
+
+    // Chars of each field of a line, where the content of selected fields is painted and
+    // everything else, e.g. quotes, is left unstyled.
+    pub fn fields(&self, line: &str, paint: impl Fn(&str) -> Vec<Char>) -> Vec<Vec<Char>> {
+        let unstyled = |range: Range<usize>| line[range].chars().map(Char::Unstyled);
+        split_fields(line, self.delimiter)
+            .into_iter()
+            .enumerate()
+            .map(|(k, field)| {
+                if !self.columns.contains(&k) {
+                    return unstyled(field).collect();
+                }
+                let content = unquote(line, field.clone());
+                let mut chars: Vec<Char> = unstyled(field.start..content.start).collect();
+                chars.extend(paint(&line[content.clone()]));
+                chars.extend(unstyled(content.end..field.end));
+                chars
+            })
+            .collect()
+    }
+
+    pub fn header_fields(&self) -> Vec<Vec<Char>> {
+        self.fields(&self.header, |text| text.chars().map(Char::Unstyled).collect())
+    }
+
+    // Indent selected fields that aren't quoted if others in their column are, so the content of
+    // each row starts at the same char index.
+    pub fn align_quotes(&self, rows: &mut [Vec<Vec<Char>>]) {
+        let quoted = |field: &Vec<Char>| matches!(field.first(), Some(Char::Unstyled('"')));
+        for &k in &self.columns {
+            if rows.iter().any(|row| row.get(k).is_some_and(quoted)) {
+                for field in rows.iter_mut().filter_map(|row| row.get_mut(k)).filter(|field| !quoted(field)) {
+                    field.insert(0, Char::Unstyled(' '));
+                }
+            }
+        }
+    }
+
+    // Join fields with the delimiter, padding each field but the last to the given widths if any.
+    pub fn join(&self, fields: Vec<Vec<Char>>, widths: &[usize]) -> Line {
+        let n = fields.len();
+        let mut chars = vec![];
+        for (k, mut field) in fields.into_iter().enumerate() {
+            if k + 1 < n {
+                let width = widths.get(k).copied().unwrap_or(0);
+                while field.len() < width {
+                    field.push(Char::Unstyled(' '));
+                }
+                field.push(Char::Unstyled(self.delimiter));
+            }
+            chars.extend(field);
+        }
+        Line::new(chars)
+    }
+}
+
+// Max width of each column of rows of fields.
+pub fn widths(rows: &[Vec<Vec<Char>>]) -> Vec<usize> {
+    let mut widths = vec![];
+    for row in rows {
+        for (k, field) in row.iter().enumerate() {
+            if k >= widths.len() {
+                widths.push(0);
+            }
+            widths[k] = widths[k].max(field.len());
+        }
+    }
+    widths
+}
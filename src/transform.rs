@@ -0,0 +1,125 @@
+use crate::bio::{complement, is_rna};
+use crate::records::Record;
+
+// Transformations of sequences applied before coloring.
+#[derive(Clone, Copy)]
+pub enum Transform {
+    RevComp,
+    Comp,
+    Rev,
+    // T -> U
+    ToRna,
+    // U -> T
+    ToDna,
+}
+
+impl Transform {
+    pub fn parse(s: &str) -> Result<Self, String> {
+        match s.trim().to_lowercase().as_str() {
+            "revcomp" | "rc" => Ok(Transform::RevComp),
+            "comp" => Ok(Transform::Comp),
+            "rev" => Ok(Transform::Rev),
+            "rna" => Ok(Transform::ToRna),
+            "dna" => Ok(Transform::ToDna),
+            _ => Err(format!(
+                "Transform not understood: '{s}'. Use \"revcomp\", \"comp\", \"rev\", \"rna\", or \"dna\"."
+            )),
+        }
+    }
+
+    fn reverses(self) -> bool {
+        matches!(self, Transform::RevComp | Transform::Rev)
+    }
+
+    fn apply_char(self, c: char, rna: bool) -> char {
+        match self {
+            Transform::RevComp | Transform::Comp => complement(c, rna),
+            Transform::Rev => c,
+            Transform::ToRna => match c {
+                'T' => 'U',
+                't' => 'u',
+                c => c,
+            },
+            Transform::ToDna => match c {
+                'U' => 'T',
+                'u' => 't',
+                c => c,
+            },
+        }
+    }
+}
+
+// Reverse the chars of lines as if they were one string, keeping the original line lengths.
+// Only chars in slots are moved, e.g. letters but not the numbers and spaces of GenBank blocks.
+fn reverse_wrapped(lines: &mut [String], is_slot: impl Fn(char) -> bool) {
+    let mut chars: Vec<char> = lines.iter().flat_map(|line| line.chars()).filter(|&c| is_slot(c)).collect();
+    chars.reverse();
+    let mut chars = chars.into_iter();
+    for line in lines.iter_mut() {
+        *line = line
+            .chars()
+            .map(|c| if is_slot(c) { chars.next().unwrap() } else { c })
+            .collect();
+    }
+}
+
+// Parse a 1-based inclusive range of columns "START-END", "START-", or "-END".
+// Returns a 0-based start and exclusive end.
+pub fn parse_columns(s: &str) -> Result<(usize, Option<usize>), String> {
+    let err = || format!("Columns not understood: '{s}'. Use START-END, START-, or -END.");
+    let number = |n: &str| n.replace(',', "").parse::<usize>().map_err(|_| err());
+    let (start, end) = match s.split_once('-') {
+        None => (number(s)?, Some(number(s)?)),
+        Some(("", end)) => (1, Some(number(end)?)),
+        Some((start, "")) => (number(start)?, None),
+        Some((start, end)) => (number(start)?, Some(number(end)?)),
+    };
+    if start == 0 || end.is_some_and(|end| end < start) {
+        return Err(err());
+    }
+    Ok((start - 1, end))
+}
+
+// Keep only the given columns of the sequence of a record, and the matching qualities, joined
+// onto a single line. GenBank and EMBL records are left unchanged.
+pub fn slice(record: &mut Record<String>, (start, end): (usize, Option<usize>)) {
+    if record.blocks {
+        return;
+    }
+    let cut = |lines: &mut Vec<String>| {
+        if lines.is_empty() {
+            return;
+        }
+        let chars = lines.iter().flat_map(|line| line.chars());
+        let n = end.map_or(usize::MAX, |end| end - start);
+        *lines = vec![chars.skip(start).take(n).collect()];
+    };
+    cut(&mut record.seq);
+    cut(&mut record.qual);
+}
+
+// Apply transforms in the given order to the sequence of a record, and its qualities if
+// reversing. Gaps and unknown chars are kept as is. The suffix is added to the header.
+// For GenBank and EMBL records only the letters of sequence blocks are transformed.
+// Lines without a header aren't sequence records, e.g. "id\tACGT", and are left unchanged.
+pub fn transform(record: &mut Record<String>, transforms: &[Transform], suffix: Option<&str>) {
+    if record.header.is_none() {
+        return;
+    }
+    let blocks = record.blocks;
+    let is_slot = |c: char| !blocks || c.is_alphabetic();
+    for &t in transforms {
+        // Checked for each transform as converting to RNA or DNA changes it.
+        let rna = is_rna(&record.seq);
+        for line in record.seq.iter_mut() {
+            *line = line.chars().map(|c| if is_slot(c) { t.apply_char(c, rna) } else { c }).collect();
+        }
+        if t.reverses() {
+            reverse_wrapped(&mut record.seq, is_slot);
+            reverse_wrapped(&mut record.qual, |_| true);
+        }
+    }
+    if let (Some(header), Some(suffix)) = (record.header.as_mut(), suffix) {
+        header.push_str(suffix);
+    }
+}
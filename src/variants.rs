@@ -0,0 +1,203 @@
+use anyhow::{Result, bail};
+use std::collections::HashMap;
+use std::io::BufRead;
+use yansi::{Painted, Style};
+
+use crate::ansi_colors::{Char, Highlight};
+use crate::bio::is_gap;
+use crate::inout::open;
+use crate::render::{Line, restyle, sequence};
+
+pub struct Variant {
+    // 0-based position of the first REF base.
+    pub pos: usize,
+    pub reference: String,
+    pub alts: Vec<String>,
+    // Allele indices of the genotype of each sample, where 0 is REF and None is missing.
+    pub genotypes: Vec<Vec<Option<usize>>>,
+}
+
+pub struct Vcf {
+    pub samples: Vec<String>,
+    // Variants keyed by CHROM.
+    pub variants: HashMap<String, Vec<Variant>>,
+}
+
+pub fn read_vcf(path: &str) -> Result<Vcf> {
+    let mut vcf = Vcf { samples: vec![], variants: HashMap::new() };
+    for (i, line) in open(path)?.lines().enumerate() {
+        let line = line?;
+        if line.starts_with("##") || line.is_empty() {
+            continue;
+        }
+        let fields: Vec<&str> = line.split('\t').collect();
+        if let Some(header) = line.strip_prefix('#') {
+            vcf.samples = header.split('\t').skip(9).map(String::from).collect();
+            continue;
+        }
+        if fields.len() < 5 {
+            bail!("{path}:{}: VCF line needs at least 5 columns.", i + 1);
+        }
+        let Ok(pos) = fields[1].parse::<usize>() else {
+            bail!("{path}:{}: VCF position not understood: '{}'.", i + 1, fields[1]);
+        };
+        let alts: Vec<String> = fields[4].split(',').map(String::from).collect();
+        if fields[3].is_empty() || alts.iter().any(|alt| alt.is_empty()) {
+            bail!("{path}:{}: VCF REF and ALT alleles can't be empty.", i + 1);
+        }
+        // GT is always first in FORMAT if present.
+        let has_gt = fields.get(8).is_some_and(|format| format.starts_with("GT"));
+        let genotypes = if has_gt {
+            fields[9..]
+                .iter()
+                .map(|sample| {
+                    let gt = sample.split(':').next().unwrap_or(".");
+                    gt.split(['/', '|']).map(|a| a.parse().ok()).collect()
+                })
+                .collect()
+        } else {
+            vec![]
+        };
+        vcf.variants.entry(fields[0].to_string()).or_default().push(Variant {
+            pos: pos.saturating_sub(1),
+            reference: fields[3].to_string(),
+            alts,
+            genotypes,
+        });
+    }
+    Ok(vcf)
+}
+
+#[derive(Clone, Copy, PartialEq)]
+enum Kind {
+    Snv,
+    Insertion,
+    Deletion,
+}
+
+// Positions (0-based ungapped) affected by an alt allele, and the glyph to show for each.
+fn effect(variant: &Variant, alt: &str) -> Option<(Kind, Vec<(usize, char)>)> {
+    // Symbolic and missing alleles, e.g. <DEL> and *, are not shown.
+    if alt.starts_with('<') || alt == "*" || alt == "." {
+        return None;
+    }
+    let reference: Vec<char> = variant.reference.chars().collect();
+    let alt: Vec<char> = alt.chars().collect();
+    let pos = variant.pos;
+    if reference.len() == alt.len() {
+        let changes = (0..alt.len())
+            .filter(|&i| !reference[i].eq_ignore_ascii_case(&alt[i]))
+            .map(|i| (pos + i, alt[i]))
+            .collect();
+        Some((Kind::Snv, changes))
+    } else if alt.len() > reference.len() {
+        // Insertion after the last REF base which is shared with ALT.
+        Some((Kind::Insertion, vec![(pos + reference.len() - 1, '+')]))
+    } else {
+        let deleted = (pos + alt.len()..pos + reference.len()).map(|p| (p, '-')).collect();
+        Some((Kind::Deletion, deleted))
+    }
+}
+
+pub struct VariantOpts {
+    pub vcf: Vcf,
+    pub snv: Highlight,
+    pub insertion: Highlight,
+    pub deletion: Highlight,
+    // Show a row for each sample with its genotype.
+    pub genotypes: bool,
+}
+
+impl VariantOpts {
+    fn highlight(&self, kind: Kind) -> Highlight {
+        match kind {
+            Kind::Snv => self.snv,
+            Kind::Insertion => self.insertion,
+            Kind::Deletion => self.deletion,
+        }
+    }
+}
+
+// Highlight variants on the reference sequence of a record given by its ID, and optionally show
+// genotype rows beneath each line. In genotype rows "." is homozygous reference, heterozygous
+// alleles are dimmed, SNVs show the alt base, insertions "+", and deletions "-". Genotype rows
+// start with their sample name, and the sequence lines with the ID, padded to the same width.
+pub fn paint_variants(seq: &mut [Line], id: &str, styles: &HashMap<char, Style>, opts: &VariantOpts) {
+    let Some(variants) = opts.vcf.variants.get(id) else {
+        return;
+    };
+    let (positions, chars) = sequence(seq);
+    let residues: Vec<usize> = (0..chars.len()).filter(|&k| !is_gap(chars[k])).collect();
+    let position = |p: usize| residues.get(p).map(|&k| positions[k]);
+
+    let n_samples = if opts.genotypes { opts.vcf.samples.len() } else { 0 };
+    let mut tracks: Vec<Vec<Vec<Char>>> = (0..n_samples)
+        .map(|_| {
+            seq.iter()
+                .map(|line| (0..line.chars.len()).map(|_| Char::Unstyled(' ')).collect())
+                .collect()
+        })
+        .collect();
+
+    for variant in variants {
+        for (a, alt) in variant.alts.iter().enumerate() {
+            let Some((kind, changes)) = effect(variant, alt) else {
+                continue;
+            };
+            let hl = opts.highlight(kind);
+            for &(p, _) in &changes {
+                if let Some(pos) = position(p) {
+                    restyle(seq, pos, |style| hl.apply(style));
+                }
+            }
+            for (sample, gt) in variant.genotypes.iter().enumerate().take(n_samples) {
+                let carried = gt.iter().filter(|&&allele| allele == Some(a + 1)).count();
+                if carried == 0 {
+                    continue;
+                }
+                let het = carried < gt.len();
+                for &(p, glyph) in &changes {
+                    let Some((i, j)) = position(p) else { continue };
+                    let mut style = styles.get(&glyph).copied().unwrap_or_default();
+                    if het {
+                        style = style.dim();
+                    }
+                    tracks[sample][i][j] = Char::Styled(Painted { value: glyph, style });
+                }
+            }
+        }
+        // Homozygous reference at the first REF base.
+        for (sample, gt) in variant.genotypes.iter().enumerate().take(n_samples) {
+            if !gt.is_empty()
+                && gt.iter().all(|&allele| allele == Some(0))
+                && let Some((i, j)) = position(variant.pos)
+                && matches!(tracks[sample][i][j], Char::Unstyled(_))
+            {
+                tracks[sample][i][j] = Char::Styled(Painted { value: '.', style: Style::new() });
+            }
+        }
+    }
+
+    if n_samples == 0 {
+        return;
+    }
+    let width = opts.vcf.samples.iter().map(|s| s.chars().count()).chain([id.chars().count()]).max().unwrap_or(0);
+    let label = |name: &str| format!("{name:<width$} ").chars().map(Char::Unstyled).collect::<Vec<_>>();
+    for line in seq.iter_mut() {
+        line.chars.splice(0..0, label(id));
+        // Rows already beneath the line, e.g. translations, are shifted along.
+        for track in &mut line.tracks {
+            track.splice(0..0, label(""));
+        }
+    }
+    for (name, sample_tracks) in opts.vcf.samples.iter().zip(tracks) {
+        for (line, mut track) in seq.iter_mut().zip(sample_tracks) {
+            track.splice(0..0, label(name));
+            // Padding only up to the last genotype.
+            while let Some(Char::Unstyled(' ')) = track.last() {
+                track.pop();
+            }
+            line.tracks.push(track);
+        }
+    }
+}
@@ -0,0 +1,44 @@
+#!/usr/bin/env bash
+# Throughput of streaming seqcol compared to plain cat on a generated fastq file.
+# Usage: tests/bench.sh [MEGABYTES]
+set -e
+cd "$(dirname "$0")"
+MB=${1:-200}
+BIN=../target/release/seqcol
+FASTQ=$(mktemp)
+trap 'rm -f "$FASTQ"' EXIT
+
+cargo build --release -q
+
+# A pool of random 150 bp reads repeated to the requested size.
+awk -v mb="$MB" 'BEGIN {
+    srand(1)
+    split("ACGT", bases, "")
+    for (i = 0; i < 1000; i++) {
+        seq = ""
+        for (j = 0; j < 150; j++) seq = seq bases[int(rand() * 4) + 1]
+        pool[i] = seq
+    }
+    qual = sprintf("%150s", ""); gsub(/ /, "I", qual)
+    n = int(mb * 1024 * 1024 / 320)
+    for (i = 0; i < n; i++) printf "@read%d\n%s\n+\n%s\n", i, pool[i % 1000], qual
+}' > "$FASTQ"
+
+bench() {
+    local name=$1
+    shift
+    local start end
+    start=$(date +%s.%N)
+    "$@" < "$FASTQ" > /dev/null
+    end=$(date +%s.%N)
+    awk -v name="$name" -v s="$start" -v e="$end" -v mb="$MB" \
+        'BEGIN { printf "%-40s %7.2f s %9.1f MB/s\n", name, e - s, mb / (e - s) }'
+}
+
+export COLORTERM=truecolor
+bench "cat" cat
+bench "seqcol -s jalview_nucl" $BIN -p never -s jalview_nucl
+bench "seqcol -s jalview_nucl -r '.*'" $BIN -p never -s jalview_nucl -r '.*'
+bench "seqcol -s jalview_nucl -a dna -m 20" $BIN -p never -s jalview_nucl -a dna -m 20
+bench "seqcol -S jalview_nucl (foreground)" $BIN -p never -s '' -S jalview_nucl
+bench "seqcol -s jalview_nucl --threads 0" $BIN -p never -s jalview_nucl --threads 0
@@ -3,9 +3,213 @@ cd $0:h
 
 COLORTERM=truecolor ../target/release/seqcol -s ./data/custom.csv ./data/custom.fa > ./expected/custom_colorscheme.txt
 
+COLORTERM=truecolor ../target/release/seqcol -s ./data/custom_utf8.csv ./data/utf8.fa > ./expected/custom_colorscheme_utf8.txt
+
 grep -v '^>' ./data/aln_picorna.fa1  | COLORTERM=truecolor ../target/release/seqcol -Ts helix_propensity_aa > ./expected/transpose_helix_propensity.txt
 
 COLORTERM=truecolor ../target/release/seqcol ./data/ebola_virus_reduced_align.fa1 -s '' -S hydrophobicity_aa -c '128 128 128' > ./expected/foreground_consensus.txt
 
 COLORTERM=truecolor ../target/release/seqcol ./data/ebola_virus_reduced_align.fa1 -s '' -S hydrophobicity_aa -C '128 128 128' > ./expected/foreground_mutations.txt
 
+
+COLORTERM=truecolor ../target/release/seqcol --pileup ./data/pileup_clipped.sam --ref ./data/pileup_ref.fa -s jalview_nucl > ./expected/pileup_clipped.txt
+
+COLORTERM=truecolor ../target/release/seqcol --pileup ./data/pileup_clipped.sam -s jalview_nucl > ./expected/pileup_clipped_noref.txt
+
+COLORTERM=truecolor ../target/release/seqcol --pileup ./data/pileup_bad.sam > ./expected/pileup_bad.txt 2>&1
+
+COLORTERM=truecolor ../target/release/seqcol --pileup ./data/pileup_short_qual.sam > ./expected/pileup_short_qual.txt 2>&1
+
+COLORTERM=truecolor ../target/release/seqcol --pileup ./data/pileup_far.sam -s jalview_nucl > ./expected/pileup_far.txt
+
+COLORTERM=truecolor ../target/release/seqcol -s jalview_nucl ./data/small.phy > ./expected/phylip.txt
+
+COLORTERM=truecolor ../target/release/seqcol -s clustal_aa --from nexus < ./data/small.nex > ./expected/nexus_stdin.txt
+
+../target/release/seqcol ./data/small.nex --to phylip-strict > ./expected/nexus_to_phylip.txt
+
+COLORTERM=truecolor ../target/release/seqcol -s jalview_nucl ./data/numbers.txt > ./expected/numbers_not_phylip.txt
+
+../target/release/seqcol ./data/bad.phy > ./expected/phylip_bad.txt 2>&1
+
+COLORTERM=truecolor ../target/release/seqcol -s clustal_aa ./data/small.pdb > ./expected/pdb_seqres.txt
+
+COLORTERM=truecolor ../target/release/seqcol -s clustal_aa --pdb-seq observed ./data/small.pdb > ./expected/pdb_observed.txt
+
+COLORTERM=truecolor ../target/release/seqcol --bfactor=plddt ./data/small.pdb > ./expected/pdb_plddt.txt
+
+COLORTERM= ../target/release/seqcol --bfactor=range ./data/small.pdb > ./expected/pdb_range_256.txt
+
+COLORTERM=truecolor ../target/release/seqcol -s clustal_aa ./data/models.pdb ./data/small.pdb ./data/small.pdb > ./expected/pdb_files.txt
+
+COLORTERM=truecolor ../target/release/seqcol -s clustal_aa ./data/small.cif > ./expected/mmcif.txt
+
+COLORTERM=truecolor ../target/release/seqcol -s clustal_aa ./data/empty_loop.cif > ./expected/mmcif_empty_loop.txt
+
+COLORTERM=truecolor ../target/release/seqcol --bfactor=plddt --from mmcif < ./data/small.cif > ./expected/mmcif_stdin.txt
+
+COLORTERM=truecolor ../target/release/seqcol -s clustal_aa ./data/header.txt > ./expected/header_not_pdb.txt
+
+../target/release/seqcol --bfactor=rainbow ./data/small.pdb > ./expected/pdb_bad_scale.txt 2>&1
+
+../target/release/seqcol --from xyz ./data/small.pdb > ./expected/from_bad.txt 2>&1
+
+COLORTERM= ../target/release/seqcol --pileup ./data/pileup_clipped.sam --quality -s jalview_nucl > ./expected/pileup_quality_256.txt
+
+COLORTERM=truecolor ../target/release/seqcol -c bold --weights henikoff --consensus-ties iupac ./data/henikoff_ties.fa > ./expected/henikoff_ties.txt
+
+COLORTERM=truecolor ../target/release/seqcol -C red --weights henikoff --consensus-threshold 0.6 ./data/henikoff_ties.fa > ./expected/henikoff_threshold.txt
+
+../target/release/seqcol -c bold --weights uniform ./data/henikoff_ties.fa > ./expected/weights_bad.txt 2>&1
+
+COLORTERM=truecolor ../target/release/seqcol --sort consensus -c bold ./data/sort.fa > ./expected/sort_consensus.txt
+
+COLORTERM=truecolor ../target/release/seqcol --sort consensus -c bold --consensus-case fold --exclude-gaps ./data/sort.fa > ./expected/sort_consensus_fold.txt
+
+COLORTERM=truecolor ../target/release/seqcol --sort identity:d ./data/sort.fa > ./expected/sort_identity.txt
+
+../target/release/seqcol --sort identity:z ./data/sort.fa > ./expected/sort_missing_id.txt 2>&1
+
+COLORTERM=truecolor ../target/release/seqcol --ansi keep -s jalview_nucl ./data/ansi.fa > ./expected/ansi_keep.txt
+
+COLORTERM=truecolor ../target/release/seqcol -c bold ./data/ansi.fa > ./expected/ansi_strip_consensus.txt
+
+../target/release/seqcol --ansi keep -c bold ./data/ansi.fa > ./expected/ansi_keep_consensus.txt 2>&1
+
+COLORTERM=truecolor ../target/release/seqcol --qc=ambiguous,homopolymer,frameshift --homopolymer 4 -s jalview_nucl ./data/qc.fa > ./expected/qc.txt 2>&1
+
+COLORTERM=truecolor ../target/release/seqcol --qc=frameshift,stop --homopolymer 4 ./data/qc.fa > ./expected/qc_frameshift_stop.txt 2>&1
+
+COLORTERM=truecolor ../target/release/seqcol --qc --homopolymer 4 ./data/qc.fa > ./expected/qc_all.txt 2>&1
+
+../target/release/seqcol --qc=gc ./data/qc.fa > ./expected/qc_bad.txt 2>&1
+
+../target/release/seqcol --qc=alphabet ./data/qc.fa > ./expected/qc_alphabet_missing.txt 2>&1
+
+COLORTERM=truecolor ../target/release/seqcol -s clustal_aa diff ./data/diff.fa > ./expected/diff.txt
+
+COLORTERM=truecolor ../target/release/seqcol -s jalview_nucl diff --mode local ./data/unaligned.fa:s1 ACGTTCGTTAGC > ./expected/diff_local.txt
+
+../target/release/seqcol diff $(printf 'A%.0s' {1..17000}) $(printf 'C%.0s' {1..17000}) > ./expected/diff_too_long.txt 2>&1
+
+../target/release/seqcol diff ./data/unaligned.fa:s9 ./data/diff.fa > ./expected/diff_missing_id.txt 2>&1
+
+COLORTERM=truecolor ../target/release/seqcol --align -s jalview_nucl -c bold ./data/unaligned.fa > ./expected/align.txt
+
+COLORTERM=truecolor ../target/release/seqcol --codon -s clustal_aa --start bold ./data/codon.fa > ./expected/codon.txt
+
+COLORTERM=truecolor ../target/release/seqcol -s jalview_nucl --translate=all ./data/codon.fa > ./expected/translate_all.txt
+
+COLORTERM=truecolor ../target/release/seqcol --codon --frame -2 --table 2 -s clustal_aa ./data/codon.fa > ./expected/codon_frame_table.txt
+
+COLORTERM=truecolor ../target/release/seqcol --translate --table 31 -s clustal_aa ./data/codon.fa > ./expected/translate_table31.txt
+
+../target/release/seqcol --codon --frame 4 ./data/codon.fa > ./expected/codon_bad_frame.txt 2>&1
+
+COLORTERM=truecolor ../target/release/seqcol -t revcomp --suffix /rc -s jalview_nucl ./data/revcomp.fq > ./expected/revcomp.txt
+
+COLORTERM=truecolor ../target/release/seqcol -t rev,rna -s jalview_nucl ./data/codon.fa > ./expected/rev_rna.txt
+
+COLORTERM=truecolor ../target/release/seqcol -t revcomp -s jalview_nucl ./data/rna.fa > ./expected/revcomp_rna.txt
+
+COLORTERM=truecolor ../target/release/seqcol -t revcomp ./data/plain_ids.tsv > ./expected/revcomp_plain.txt
+
+../target/release/seqcol -t flip ./data/codon.fa > ./expected/transform_bad.txt 2>&1
+
+COLORTERM=truecolor ../target/release/seqcol --repeats -s jalview_nucl ./data/repeats.fa > ./expected/repeats.txt
+
+COLORTERM=truecolor ../target/release/seqcol --repeats=tandem --tandem 9 --repeat-style underline ./data/repeats.fa > ./expected/repeats_tandem.txt
+
+../target/release/seqcol --repeats=satellite ./data/repeats.fa > ./expected/repeats_bad.txt 2>&1
+
+COLORTERM=truecolor ../target/release/seqcol --bed ./data/annot.bed -s jalview_nucl ./data/annot.fa > ./expected/bed_rows.txt
+
+COLORTERM=truecolor ../target/release/seqcol --gff ./data/annot.gff --annotate color ./data/annot.fa > ./expected/gff_color.txt
+
+COLORTERM=truecolor ../target/release/seqcol --gff ./data/annot.gff --annotate underline -s jalview_nucl ./data/annot.fa > ./expected/gff_underline.txt
+
+../target/release/seqcol --bed ./data/bad.bed ./data/annot.fa > ./expected/bed_bad.txt 2>&1
+
+COLORTERM= ../target/release/seqcol --bed ./data/annot.bed ./data/annot.fa > ./expected/bed_rows_256.txt
+
+COLORTERM=truecolor ../target/release/seqcol --ss ./data/ss_dssp.fa -s clustal_aa ./data/ss.fa > ./expected/ss_dssp.txt
+
+COLORTERM=truecolor ../target/release/seqcol --ss ./data/ss.sto --ss-tint -s clustal_aa ./data/ss.fa > ./expected/ss_stockholm_tint.txt
+
+COLORTERM= ../target/release/seqcol --ss p2=./data/ss_plain.txt --ss-tint -s clustal_aa ./data/ss.fa > ./expected/ss_literal_256.txt
+
+../target/release/seqcol --ss ./data/missing.ss2 ./data/ss.fa > ./expected/ss_missing.txt 2>&1
+
+COLORTERM=truecolor ../target/release/seqcol --vcf ./data/small.vcf -s jalview_nucl ./data/vcf_ref.fa > ./expected/vcf.txt
+
+COLORTERM=truecolor ../target/release/seqcol --vcf ./data/small.vcf --genotypes --snv red --del strike ./data/vcf_ref.fa > ./expected/vcf_genotypes.txt
+
+../target/release/seqcol --vcf ./data/bad.vcf ./data/vcf_ref.fa > ./expected/vcf_bad.txt 2>&1
+
+../target/release/seqcol --vcf ./data/empty_ref.vcf ./data/vcf_ref.fa > ./expected/vcf_empty_ref.txt 2>&1
+
+../target/release/seqcol --gff ./data/bad.gff ./data/annot.fa > ./expected/gff_bad.txt 2>&1
+
+COLORTERM=truecolor ../target/release/seqcol --features -s jalview_nucl ./data/small.gb > ./expected/genbank_features.txt
+
+COLORTERM=truecolor ../target/release/seqcol --features=all --annotate color ./data/small.gb > ./expected/genbank_features_color.txt
+
+COLORTERM=truecolor ../target/release/seqcol --features --translate -s jalview_nucl ./data/small.embl > ./expected/embl_features.txt
+
+../target/release/seqcol --to phylip ./data/small.gb > ./expected/genbank_to_phylip.txt 2>&1
+
+COLORTERM=truecolor ../target/release/seqcol --table-cols cdr3,sequence -s clustal_aa ./data/table.csv > ./expected/table_cols.txt
+
+COLORTERM=truecolor ../target/release/seqcol --table-cols 3 --table-align -c bold -s clustal_aa ./data/table.csv > ./expected/table_align_consensus.txt
+
+COLORTERM=truecolor ../target/release/seqcol --table-cols seq --delimiter tab -s jalview_nucl ./data/table.tsv > ./expected/table_tsv.txt
+
+../target/release/seqcol --table-cols junction ./data/table.csv > ./expected/table_missing_col.txt 2>&1
+
+COLORTERM=truecolor ../target/release/seqcol --json-fields chains.seq -s clustal_aa ./data/chains.jsonl > ./expected/json_fields.txt
+
+COLORTERM=truecolor ../target/release/seqcol --json-fields 'chains.*' --json-pretty -s clustal_aa ./data/chains.jsonl > ./expected/json_pretty.txt
+
+../target/release/seqcol --json-fields seq ./data/unterminated.json > ./expected/json_unterminated.txt 2>&1
+
+rm -f ./data/region.fa.fai ./data/region.fa.gz.fai ./data/region.fa.gz.gzi
+
+COLORTERM=truecolor ../target/release/seqcol --region seq1:45-54 --region seq3 -s jalview_nucl ./data/region.fa > ./expected/region.txt
+
+cat ./data/region.fa.fai > ./expected/region_fai.txt
+
+COLORTERM=truecolor ../target/release/seqcol --region seq2:5-25 --region seq1:48-52 -s jalview_nucl ./data/region.fa.gz > ./expected/region_bgzf.txt
+
+../target/release/seqcol --region seq9 ./data/region.fa > ./expected/region_missing.txt 2>&1
+
+../target/release/seqcol --region seq1 ./data/plain_gzip.fa.gz > ./expected/region_gzip.txt 2>&1
+
+COLORTERM=truecolor ../target/release/seqcol --select h1,m2 -c bold ./data/groups.fa > ./expected/select.txt
+
+COLORTERM=truecolor ../target/release/seqcol --select-file ./data/ids.txt --consensus-all -c bold ./data/groups.fa > ./expected/select_file_consensus_all.txt
+
+COLORTERM=truecolor ../target/release/seqcol --grep-header 'sapiens|musculus' --grep-seq 'g-?aattc|CC.AG' -s jalview_nucl ./data/groups.fa > ./expected/grep.txt
+
+../target/release/seqcol --grep-seq '(' ./data/groups.fa > ./expected/grep_bad.txt 2>&1
+
+COLORTERM=truecolor ../target/release/seqcol --group-regex '\[(.*)\]' -c bold ./data/groups.fa > ./expected/groups_regex.txt
+
+COLORTERM=truecolor ../target/release/seqcol --group-file ./data/groups.tsv -C red ./data/groups.fa > ./expected/groups_file.txt
+
+../target/release/seqcol --group-file ./data/missing.tsv -c bold ./data/groups.fa > ./expected/groups_missing.txt 2>&1
+
+COLORTERM=truecolor ../target/release/seqcol --case dim -s jalview_nucl ./data/softmask.fa > ./expected/case_dim.txt
+
+COLORTERM=truecolor ../target/release/seqcol --case hide -s clustal_aa ./data/softmask.fa > ./expected/case_hide.txt
+
+COLORTERM=truecolor ../target/release/seqcol --case insensitive --threads 4 -s jalview_nucl ./data/softmask.fa ./data/groups.fa > ./expected/case_insensitive_threads.txt
+
+../target/release/seqcol --case shout ./data/softmask.fa > ./expected/case_bad.txt 2>&1
+
+COLORTERM=truecolor ../target/release/seqcol --threads 2 -s jalview_nucl <(cat ./data/codon.fa) ./data/rna.fa ./data/codon.fa > ./expected/threads_files.txt
+
+cat ./data/groups.fa | COLORTERM=truecolor ../target/release/seqcol -c bold --weights henikoff - > ./expected/consensus_stdin.txt
+
+COLORTERM=truecolor ../target/release/seqcol -c bold --weights henikoff <(cat ./data/groups.fa) > ./expected/consensus_fifo.txt
+
+../target/release/seqcol -c bold --region seq1 - < ./data/region.fa > ./expected/region_stdin.txt 2>&1